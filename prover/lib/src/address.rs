@@ -0,0 +1,63 @@
+// prover/lib/src/address.rs
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// The address a bill was issued to, normalized by the country validator so that the same
+/// address printed differently on two bills compares equal.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Address {
+    /// The street, lowercase and without accents, with its type spelled out, e.g. `avenida da
+    /// liberdade`.
+    pub street: String,
+    /// The door number, e.g. `123` or `12a`, if the address has one.
+    pub number: Option<String>,
+    /// The floor and side, e.g. `3 esq` or `rc dto`, if the address is a flat.
+    pub floor: Option<String>,
+    pub postal_code: String,
+}
+
+impl Address {
+    /// The hex-encoded SHA-256 of the salt followed by the normalized address.
+    ///
+    /// Proofs made with the same salt commit the same hash for the same address, without
+    /// revealing it to anyone who does not know the salt.
+    pub fn commitment(&self, salt: &[u8; 32]) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(salt);
+        hasher.update(self.canonical().as_bytes());
+        hex::encode(hasher.finalize())
+    }
+
+    fn canonical(&self) -> String {
+        [
+            self.street.as_str(),
+            self.number.as_deref().unwrap_or(""),
+            self.floor.as_deref().unwrap_or(""),
+            self.postal_code.as_str(),
+        ]
+        .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commitment_depends_on_salt_and_every_part() {
+        let address = Address {
+            street: "rua de exemplo".to_string(),
+            number: Some("123".to_string()),
+            floor: None,
+            postal_code: "4050-123".to_string(),
+        };
+        let flat = Address {
+            floor: Some("1 esq".to_string()),
+            ..address.clone()
+        };
+
+        assert_eq!(address.commitment(&[1; 32]), address.clone().commitment(&[1; 32]));
+        assert_ne!(address.commitment(&[1; 32]), address.commitment(&[2; 32]));
+        assert_ne!(address.commitment(&[1; 32]), flat.commitment(&[1; 32]));
+    }
+}
@@ -0,0 +1,269 @@
+// prover/lib/src/claim.rs
+use crate::{Address, Country, Date, Disclosed, Freshness, Scope};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use thiserror::Error;
+
+/// Everything extracted from a bill that passed validation.
+///
+/// This never leaves the prover: only the [`ResidencyClaim`] derived from it is committed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Residency {
+    /// The fiscal document identifier, e.g. the ATCUD of a Portuguese bill.
+    pub document_id: String,
+    /// The tax number of the company that issued the bill.
+    pub issuer_tax_id: String,
+    pub issue_date: Date,
+    /// The issue date of the oldest bill, if several were combined, `issue_date` otherwise.
+    pub first_issue_date: Date,
+    /// The last day the bill proves residency for, see [`crate::Config::validity_days`].
+    pub expiry: Date,
+    /// The [`crate::Config::validity_days`] the expiry was computed with.
+    pub validity_days: u32,
+    pub postal_code: String,
+    /// The normalized address the bill was issued to, if the validator can parse it.
+    pub normalized_address: Option<Address>,
+    pub country: Country,
+    pub scope: Scope,
+    /// The place proven for `scope`: the country itself, the district or autonomous region for
+    /// [`Scope::Region`], or the municipality for [`Scope::City`].
+    pub location: String,
+    /// The attributes revealed by the claim.
+    pub disclosed: Disclosed,
+    /// The [`crate::Disclosure::commitment`] of the policy `disclosed` follows.
+    pub disclosure: String,
+    /// The fields of the fiscal QR code that matched the document identifier, keyed by their
+    /// code (e.g. `A` for the issuer NIF in Portugal). Empty for countries without one.
+    pub qr_fields: BTreeMap<String, String>,
+    /// The [`crate::TrustedIssuers::commitment`] of the allowlist the issuer was checked against.
+    pub trusted_issuers: Option<String>,
+    /// The [`crate::TrustedRoots::commitment`] of the roots the signature of the bill was checked
+    /// against.
+    pub trusted_roots: Option<String>,
+    /// The [`crate::DkimKey::commitment`] of the key the email of the bill was signed with.
+    pub dkim_key: Option<String>,
+    /// The domain that DKIM-signed the email of the bill.
+    pub dkim_domain: Option<String>,
+    /// The selector of the DKIM key under `dkim_domain`.
+    pub dkim_selector: Option<String>,
+    /// The freshness policy the issue date was checked against.
+    pub freshness: Option<Freshness>,
+    /// The [`crate::Holder::commitment`] of the claimant the bill is addressed to.
+    pub holder: Option<String>,
+    /// The [`crate::Address::commitment`] of the address the bill is issued to.
+    pub address: Option<String>,
+    /// The number of months the bills were checked to span, see
+    /// [`crate::Config::min_residency_months`].
+    pub resident_since_at_least: Option<u32>,
+    pub nullifier_domain: String,
+    /// The [`crate::nullifier`] of the bill in `nullifier_domain`, or of every bill if several
+    /// were combined.
+    pub nullifiers: Vec<String>,
+}
+
+impl Residency {
+    /// Combines bills that prove the same residency into one: the newest bill, which dates back
+    /// to the oldest bill, holds until the earliest expiry and carries the nullifiers of all the
+    /// bills.
+    ///
+    /// The bills must come from distinct issuers, so that a single issuer cannot vouch for the
+    /// residency alone, and be addressed to the same postal code, and to the same address if it
+    /// is committed. They must also be issued in distinct months, so that they show the residency
+    /// lasts rather than a single month's bills.
+    pub fn combine(residencies: Vec<Residency>) -> Result<Residency, ResidencyError> {
+        let mut issuers = BTreeSet::new();
+        if !residencies.iter().all(|r| issuers.insert(r.issuer_tax_id.as_str())) {
+            return Err(ResidencyError::DuplicateIssuer);
+        }
+        let expiry = residencies.iter().map(|r| r.expiry).min();
+        let first_issue_date = residencies.iter().map(|r| r.first_issue_date).min();
+        let nullifiers: Vec<String> = residencies
+            .iter()
+            .flat_map(|r| r.nullifiers.iter().cloned())
+            .collect();
+
+        let mut newest = residencies
+            .iter()
+            .max_by_key(|r| r.issue_date)
+            .ok_or(ResidencyError::NoDocument)?
+            .clone();
+        let same_address = |r: &Residency| match (&r.normalized_address, &newest.normalized_address) {
+            (Some(address), Some(newest)) => address == newest,
+            _ => true,
+        };
+        if !residencies.iter().all(|r| {
+            r.postal_code == newest.postal_code && r.address == newest.address && same_address(r)
+        }) {
+            return Err(ResidencyError::AddressMismatch);
+        }
+        let mut months = BTreeSet::new();
+        if !residencies
+            .iter()
+            .all(|r| months.insert((r.issue_date.year(), r.issue_date.month())))
+        {
+            return Err(ResidencyError::DuplicateMonth);
+        }
+        newest.expiry = expiry.unwrap_or(newest.expiry);
+        newest.first_issue_date = first_issue_date.unwrap_or(newest.first_issue_date);
+        newest.nullifiers = nullifiers;
+        Ok(newest)
+    }
+
+    pub fn claim(&self) -> ResidencyClaim {
+        ResidencyClaim {
+            disclosed: self.disclosed.clone(),
+            disclosure: self.disclosure.clone(),
+            validity_days: self.validity_days,
+            trusted_issuers: self.trusted_issuers.clone(),
+            trusted_roots: self.trusted_roots.clone(),
+            dkim_key: self.dkim_key.clone(),
+            dkim_domain: self.dkim_domain.clone(),
+            dkim_selector: self.dkim_selector.clone(),
+            freshness: self.freshness,
+            holder: self.holder.clone(),
+            address: self.address.clone(),
+            resident_since_at_least: self.resident_since_at_least,
+            nullifier_domain: self.nullifier_domain.clone(),
+            nullifiers: self.nullifiers.clone(),
+        }
+    }
+}
+
+/// The public part of a residency proof, committed to `HyleOutput.program_outputs`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResidencyClaim {
+    /// The attributes of the bill the application asked for, and nothing else, including the
+    /// expiry of the claim if the policy reveals it, see [`Disclosed::expiry`].
+    pub disclosed: Disclosed,
+    /// The commitment to the disclosure policy the claim follows, so verifiers can check it is
+    /// the one they requested.
+    pub disclosure: String,
+    /// The number of days the bill was taken to prove residency for, after the end of the month
+    /// it was issued in, so verifiers can tell how long a bill counts for.
+    pub validity_days: u32,
+    /// The commitment to the allowlist of issuers the bill was accepted under, if any.
+    pub trusted_issuers: Option<String>,
+    /// The commitment to the certificate authorities the bill was checked to be signed under, if
+    /// the proof required a digital signature.
+    pub trusted_roots: Option<String>,
+    /// The commitment to the DKIM public key the email of the bill was checked to be signed
+    /// with, if the proof required the bill to arrive by email.
+    pub dkim_key: Option<String>,
+    /// The domain that signed the email, e.g. `edp.pt`, so a verifier knows who sent the bill.
+    pub dkim_domain: Option<String>,
+    /// The selector the key is published under, so a verifier can look it up at
+    /// `<selector>._domainkey.<domain>` and check it against `dkim_key`.
+    pub dkim_selector: Option<String>,
+    /// The current date and maximum age the bill was checked against, if any.
+    pub freshness: Option<Freshness>,
+    /// A salted hash of the claimant's identity, binding the proof to them without revealing it.
+    pub holder: Option<String>,
+    /// A salted hash of the normalized address, so that proofs made with the same salt can be
+    /// told to refer to the same address without revealing it.
+    pub address: Option<String>,
+    /// Proves that the bills, all addressed to the same place, span at least this many months,
+    /// without revealing their dates.
+    pub resident_since_at_least: Option<u32>,
+    /// The application the nullifier was derived for. Contracts must check it is their own,
+    /// otherwise a bill could be reused under another domain.
+    pub nullifier_domain: String,
+    /// Identify the bills within the application without revealing them, so that a bill can only
+    /// be used for one proof. There is one per bill the claim is based on.
+    pub nullifiers: Vec<String>,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ResidencyError {
+    #[error("Failed to parse PDF: {0}")]
+    PdfParse(String),
+
+    #[error("No PDF bill attached to the email")]
+    NoPdfAttachment,
+
+    #[error("The document is encrypted with a password")]
+    EncryptedDocument,
+
+    #[error("No text found in the document, it may be a scanned image")]
+    NoText,
+
+    #[error("Country not supported: {0:?}")]
+    UnsupportedCountry(Country),
+
+    #[error("Scope not supported for this country: {0:?}")]
+    UnsupportedScope(Scope),
+
+    #[error("The document does not look like a bill from the selected country")]
+    UnrecognizedDocument,
+
+    #[error("No valid document identifier (e.g. ATCUD) found in the document")]
+    NoDocumentId,
+
+    #[error("No valid tax number of the issuer found in the document")]
+    NoIssuerTaxId,
+
+    #[error("The document was not issued by a trusted issuer")]
+    UnknownIssuer,
+
+    #[error("No valid issue date found in the document")]
+    NoIssueDate,
+
+    #[error("The document is older than the maximum age allowed")]
+    StaleDocument,
+
+    #[error("The document is dated after the current date")]
+    FutureIssueDate,
+
+    #[error("The document is not addressed to the claimant")]
+    HolderMismatch,
+
+    #[error("No fiscal QR code found in the document")]
+    NoQrCode,
+
+    #[error("The QR code of the document does not match its identifier")]
+    QrCodeMismatch,
+
+    #[error("The document is not digitally signed")]
+    NoSignature,
+
+    #[error("Invalid digital signature: {0}")]
+    InvalidSignature(String),
+
+    #[error("The document was not signed by a trusted certificate authority")]
+    UntrustedSigner,
+
+    #[error("The email of the bill is not DKIM-signed")]
+    NoDkimSignature,
+
+    #[error("Invalid DKIM signature: {0}")]
+    InvalidDkimSignature(String),
+
+    #[error("No document to prove residency with")]
+    NoDocument,
+
+    #[error("Two of the documents were issued by the same issuer")]
+    DuplicateIssuer,
+
+    #[error("Two of the documents were issued in the same month")]
+    DuplicateMonth,
+
+    #[error("The documents are not addressed to the same place")]
+    AddressMismatch,
+
+    #[error("The validity window is longer than the maximum allowed")]
+    ValidityTooLong,
+
+    #[error("The documents do not span the required residency period")]
+    ResidencyTooShort,
+
+    #[error("No valid postal code found in the document")]
+    NoPostalCode,
+
+    #[error("No address found in the document")]
+    NoAddress,
+
+    #[error("The issuer category is only known for known issuers")]
+    UnknownIssuerCategory,
+
+    #[error("The postal code does not resolve to a known location")]
+    UnknownPostalCode,
+}
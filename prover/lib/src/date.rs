@@ -0,0 +1,160 @@
+// prover/lib/src/date.rs
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// A calendar date, as printed on bills.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Date {
+    year: u16,
+    month: u8,
+    day: u8,
+}
+
+impl Date {
+    pub fn new(year: u16, month: u8, day: u8) -> Option<Self> {
+        let days_in_month = match month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if matches!((year % 4, year % 100, year % 400), (0, 1.., _) | (_, _, 0)) => 29,
+            2 => 28,
+            _ => return None,
+        };
+
+        (day >= 1 && day <= days_in_month).then_some(Self { year, month, day })
+    }
+
+    /// The date of a Unix timestamp, in UTC.
+    pub fn from_unix_timestamp(seconds: u64) -> Self {
+        Self::from_days((seconds / 86_400) as i64)
+    }
+
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+
+    pub fn month(&self) -> u8 {
+        self.month
+    }
+
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+
+    /// The number of days from `self` to `later`, negative if `later` is before `self`.
+    pub fn days_until(&self, later: Date) -> i64 {
+        later.days() - self.days()
+    }
+
+    /// The number of whole months from `self` to `later`, negative if `later` is before `self`.
+    /// A month is complete once `later` reaches the same day of the month as `self`.
+    pub fn months_until(&self, later: Date) -> i64 {
+        let months = |date: &Date| i64::from(date.year) * 12 + i64::from(date.month);
+        months(&later) - months(self) - i64::from(later.day < self.day)
+    }
+
+    pub fn add_days(&self, days: i64) -> Self {
+        Self::from_days(self.days() + days)
+    }
+
+    /// The last day of the month of the date.
+    pub fn end_of_month(&self) -> Self {
+        (28..=31)
+            .rev()
+            .find_map(|day| Date::new(self.year, self.month, day))
+            .unwrap_or(*self)
+    }
+
+    // Days since 1970-01-01, from http://howardhinnant.github.io/date_algorithms.html
+    fn days(&self) -> i64 {
+        let month = i64::from(self.month);
+        let year = i64::from(self.year) - i64::from(month <= 2);
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(self.day) - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146_097 + day_of_era - 719_468
+    }
+
+    // Dates past the range of years saturate to its first or last day
+    fn from_days(days: i64) -> Self {
+        let first = Date { year: 0, month: 1, day: 1 }.days();
+        let last = Date { year: 9999, month: 12, day: 31 }.days();
+        let days = days.clamp(first, last) + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days - era * 146_097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+        let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+        Self {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+        }
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl FromStr for Date {
+    type Err = String;
+
+    /// Parses an ISO 8601 date, e.g. `2024-12-07`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().splitn(3, '-').map(str::parse::<u16>);
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(Ok(year)), Some(Ok(month)), Some(Ok(day))) if month <= 12 && day <= 31 => {
+                Date::new(year, month as u8, day as u8)
+            }
+            _ => None,
+        }
+        .ok_or_else(|| format!("Invalid date: {}", s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_calendar_dates() {
+        assert!(Date::new(2024, 2, 29).is_some());
+        assert!(Date::new(2023, 2, 29).is_none());
+        assert!(Date::new(2024, 4, 31).is_none());
+        assert!(Date::new(2024, 13, 1).is_none());
+        assert_eq!("2024-12-07".parse(), Ok(Date::new(2024, 12, 7).unwrap()));
+        assert!("07/12/2024".parse::<Date>().is_err());
+    }
+
+    #[test]
+    fn counts_days_between_dates() {
+        let date = Date::new(2024, 12, 7).unwrap();
+        assert_eq!(Date::from_unix_timestamp(1_733_529_600), date);
+        assert_eq!(date.days_until(Date::new(2025, 3, 7).unwrap()), 90);
+        assert_eq!(date.add_days(-7), Date::new(2024, 11, 30).unwrap());
+        assert_eq!(date.add_days(366).to_string(), "2025-12-08");
+        assert_eq!(date.end_of_month(), Date::new(2024, 12, 31).unwrap());
+        assert_eq!(Date::new(2024, 2, 3).unwrap().end_of_month(), Date::new(2024, 2, 29).unwrap());
+        assert_eq!(Date::new(2025, 4, 30).unwrap().end_of_month(), Date::new(2025, 4, 30).unwrap());
+        // the year does not wrap around
+        assert_eq!(date.add_days(i64::from(u32::MAX)).to_string(), "9999-12-31");
+    }
+
+    #[test]
+    fn counts_whole_months_between_dates() {
+        let date = Date::new(2024, 5, 20).unwrap();
+        assert_eq!(date.months_until(Date::new(2024, 11, 20).unwrap()), 6);
+        assert_eq!(date.months_until(Date::new(2024, 11, 19).unwrap()), 5);
+        assert_eq!(date.months_until(Date::new(2025, 1, 31).unwrap()), 8);
+        assert_eq!(date.months_until(date), 0);
+        assert_eq!(Date::new(2024, 12, 7).unwrap().months_until(date), -7);
+    }
+}
@@ -0,0 +1,182 @@
+// prover/lib/src/disclosure.rs
+use crate::{Country, CountryValidator, Date, IssuerCategory, ResidencyError, Scope};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+
+/// The attributes of a bill a proof reveals, chosen by the application that requests it.
+///
+/// Proofs commit [`Disclosure::commitment`], so a verifier can tell that the attributes it
+/// receives are exactly the ones it asked for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Disclosure {
+    pub country: bool,
+    /// The district, or autonomous region, in Portugal, and the province in Spain.
+    pub region: bool,
+    pub municipality: bool,
+    /// The postal code up to the locality: the CP4 in Portugal, the province digits in Spain.
+    pub postal_prefix: bool,
+    pub issue_month: bool,
+    /// The last day the claim holds, see [`crate::ResidencyClaim`]. It is derived from the month
+    /// the bill was issued in, so it tells that month as much as `issue_month` does.
+    pub expiry: bool,
+    /// Whether the issuer is an electricity, gas, water or telecommunications company. Only
+    /// known issuers have a category.
+    pub issuer_category: bool,
+}
+
+impl Disclosure {
+    /// What proofs revealed before disclosure policies: the country, the location proven for
+    /// `scope`, the issue month and the expiry.
+    pub fn for_scope(scope: Scope) -> Self {
+        Self {
+            country: true,
+            region: scope == Scope::Region,
+            municipality: scope == Scope::City,
+            issue_month: true,
+            expiry: true,
+            ..Self::default()
+        }
+    }
+
+    /// The hex-encoded SHA-256 of the names of the revealed attributes, each followed by a
+    /// newline.
+    pub fn commitment(&self) -> String {
+        let mut hasher = Sha256::new();
+        for name in self.names() {
+            hasher.update(name.as_bytes());
+            hasher.update(b"\n");
+        }
+        hex::encode(hasher.finalize())
+    }
+
+    fn names(&self) -> Vec<&'static str> {
+        [
+            ("country", self.country),
+            ("region", self.region),
+            ("municipality", self.municipality),
+            ("postal_prefix", self.postal_prefix),
+            ("issue_month", self.issue_month),
+            ("expiry", self.expiry),
+            ("issuer_category", self.issuer_category),
+        ]
+        .into_iter()
+        .filter_map(|(name, revealed)| revealed.then_some(name))
+        .collect()
+    }
+
+    /// The attributes of a validated bill the policy reveals. Fails if one of them cannot be
+    /// found, rather than leaving it out.
+    pub(crate) fn disclose(
+        &self,
+        validator: &dyn CountryValidator,
+        country: Country,
+        postal_code: &str,
+        issuer_tax_id: &str,
+        issue_date: Date,
+        expiry: Date,
+    ) -> Result<Disclosed, ResidencyError> {
+        let issuer_category = if self.issuer_category {
+            let issuer = validator
+                .known_issuers()
+                .iter()
+                .find(|issuer| issuer.tax_id == issuer_tax_id)
+                .ok_or(ResidencyError::UnknownIssuerCategory)?;
+            Some(issuer.category)
+        } else {
+            None
+        };
+
+        Ok(Disclosed {
+            country: self.country.then_some(country),
+            region: self
+                .region
+                .then(|| validator.resolve_scope(postal_code, Scope::Region))
+                .transpose()?,
+            municipality: self
+                .municipality
+                .then(|| validator.resolve_scope(postal_code, Scope::City))
+                .transpose()?,
+            postal_prefix: self
+                .postal_prefix
+                .then(|| validator.postal_prefix(postal_code)),
+            issue_month: self
+                .issue_month
+                .then(|| format!("{:04}-{:02}", issue_date.year(), issue_date.month())),
+            expiry: self.expiry.then_some(expiry),
+            issuer_category,
+        })
+    }
+}
+
+impl FromStr for Disclosure {
+    type Err = String;
+
+    /// Parses a comma-separated list of attribute names, e.g. `country,issue_month`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut disclosure = Self::default();
+        for name in s.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            let revealed = match name {
+                "country" => &mut disclosure.country,
+                "region" => &mut disclosure.region,
+                "municipality" => &mut disclosure.municipality,
+                "postal_prefix" => &mut disclosure.postal_prefix,
+                "issue_month" => &mut disclosure.issue_month,
+                "expiry" => &mut disclosure.expiry,
+                "issuer_category" => &mut disclosure.issuer_category,
+                _ => return Err(format!("Unknown attribute: {}", name)),
+            };
+            *revealed = true;
+        }
+        Ok(disclosure)
+    }
+}
+
+/// The attributes revealed under a [`Disclosure`], `None` for the ones it leaves out.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Disclosed {
+    pub country: Option<Country>,
+    pub region: Option<String>,
+    pub municipality: Option<String>,
+    pub postal_prefix: Option<String>,
+    /// The month the bill was issued in, as `YYYY-MM`. The day is left out so the bill cannot be
+    /// told apart from the other bills of the month.
+    pub issue_month: Option<String>,
+    /// The last day the claim holds. Residency changes over time, so verifiers must not accept
+    /// the claim after it, and the holder has to prove it again with a newer bill.
+    ///
+    /// It is always the last day of a month, and only depends on the month the bill was issued
+    /// in and the validity window, so it reveals the issue month but not the day. Applications
+    /// that keep the month hidden rely on the registry instead, which checks the expiry of a
+    /// registration without revealing it.
+    pub expiry: Option<Date>,
+    pub issuer_category: Option<IssuerCategory>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_attribute_list() {
+        let disclosure: Disclosure = "country, issue_month".parse().unwrap();
+        assert_eq!(disclosure, Disclosure {
+            country: true,
+            issue_month: true,
+            ..Default::default()
+        });
+        assert_eq!("".parse(), Ok(Disclosure::default()));
+        assert!("country,address".parse::<Disclosure>().is_err());
+    }
+
+    #[test]
+    fn commitment_depends_on_revealed_attributes() {
+        let country: Disclosure = "country".parse().unwrap();
+        assert_eq!(country.commitment(), "country".parse::<Disclosure>().unwrap().commitment());
+        assert_ne!(country.commitment(), Disclosure::default().commitment());
+        assert_ne!(
+            Disclosure::for_scope(Scope::Region).commitment(),
+            Disclosure::for_scope(Scope::City).commitment()
+        );
+    }
+}
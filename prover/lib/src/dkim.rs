@@ -0,0 +1,388 @@
+// prover/lib/src/dkim.rs
+//! DKIM signatures (RFC 6376) of the emails bills are delivered with: the sending domain signs
+//! the body and the chosen header fields, and publishes its public key in DNS.
+use crate::email::{lines, Header, Part, BASE64};
+use crate::ResidencyError;
+use base64::Engine;
+use rsa::pkcs1::DecodeRsaPublicKey;
+use rsa::pkcs8::DecodePublicKey;
+use rsa::{Pkcs1v15Sign, RsaPublicKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// The public key the email of a bill must be DKIM-signed with, DER-encoded as the `p=` tag of
+/// the DNS record publishes it.
+///
+/// The program cannot query DNS, so the key is an input of the proof and proofs commit
+/// [`DkimKey::commitment`] along with the signing domain and selector: a verifier checks the commitment
+/// against the key the domain publishes, e.g. at `<selector>._domainkey.edp.pt`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DkimKey {
+    public_key: Vec<u8>,
+}
+
+impl DkimKey {
+    pub fn new(public_key: Vec<u8>) -> Self {
+        Self { public_key }
+    }
+
+    /// The hex-encoded SHA-256 of the DER-encoded public key.
+    pub fn commitment(&self) -> String {
+        hex::encode(Sha256::digest(&self.public_key))
+    }
+
+    // DNS records hold a SubjectPublicKeyInfo, some older ones the bare PKCS#1 key
+    fn rsa_public_key(&self) -> Option<RsaPublicKey> {
+        RsaPublicKey::from_public_key_der(&self.public_key)
+            .or_else(|_| RsaPublicKey::from_pkcs1_der(&self.public_key))
+            .ok()
+    }
+}
+
+impl FromStr for DkimKey {
+    type Err = String;
+
+    /// Parses the TXT record of the key, e.g. `v=DKIM1; k=rsa; p=MIIBIjANBg...`, or the
+    /// base64-encoded key alone.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tags = tags(s);
+        if tags.get("k").is_some_and(|k| k != "rsa") {
+            return Err("only RSA keys are supported".to_string());
+        }
+        let key = match tags.get("p") {
+            Some(key) => key.clone(),
+            None => s.split_whitespace().collect(),
+        };
+        match BASE64.decode(key) {
+            Ok(public_key) if !public_key.is_empty() => Ok(Self::new(public_key)),
+            // an empty `p=` tag means the key was revoked
+            _ => Err("no public key in the record".to_string()),
+        }
+    }
+}
+
+/// Who signed an email: the domain, and the selector its key is published under in the DNS of
+/// that domain, at `<selector>._domainkey.<domain>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Signer {
+    pub(crate) domain: String,
+    pub(crate) selector: String,
+}
+
+/// Checks that `input`, an email, carries a DKIM signature made with `key` over its body and
+/// its `From` field, and returns who signed it.
+///
+/// The signing domain must be the domain of the `From` address or a parent of it, as DMARC
+/// relaxed alignment requires: any domain can sign any email, and a signature by another domain
+/// says nothing about the sender the bill claims to be from.
+///
+/// Only `rsa-sha256` signatures over the whole body are accepted, with any of the `simple` and
+/// `relaxed` canonicalizations.
+pub(crate) fn verify(input: &[u8], key: &DkimKey) -> Result<Signer, ResidencyError> {
+    let message = Part::parse(input).ok_or(ResidencyError::NoDkimSignature)?;
+    let public_key = key
+        .rsa_public_key()
+        .ok_or_else(|| ResidencyError::InvalidDkimSignature("not an RSA public key".to_string()))?;
+
+    // a message may be signed several times, e.g. by the sender and by its mail provider
+    let mut error = ResidencyError::NoDkimSignature;
+    for signature in message
+        .headers
+        .iter()
+        .filter(|header| header.name.eq_ignore_ascii_case("DKIM-Signature"))
+    {
+        match verify_signature(&message, signature, &public_key) {
+            Ok(signer) => return Ok(signer),
+            Err(e) => error = e,
+        }
+    }
+    Err(error)
+}
+
+fn verify_signature(
+    message: &Part,
+    signature: &Header,
+    public_key: &RsaPublicKey,
+) -> Result<Signer, ResidencyError> {
+    let invalid = |reason: &str| ResidencyError::InvalidDkimSignature(reason.to_string());
+    let value = signature.value();
+    let tags = tags(&value);
+    let tag = |name: &str| tags.get(name).ok_or_else(|| invalid(&format!("missing {}= tag", name)));
+
+    if tag("v")? != "1" {
+        return Err(invalid("unsupported version"));
+    }
+    if tag("a")? != "rsa-sha256" {
+        return Err(invalid("unsupported algorithm"));
+    }
+    // a body length limit would let anything be appended to the signed body
+    if tags.contains_key("l") {
+        return Err(invalid("partial body signatures are not accepted"));
+    }
+    let canonicalization = tags.get("c").map(String::as_str).unwrap_or("simple");
+    let (relaxed_headers, relaxed_body) = match canonicalization.split_once('/') {
+        Some((headers, body)) => (is_relaxed(headers)?, is_relaxed(body)?),
+        None => (is_relaxed(canonicalization)?, false),
+    };
+    let domain = tag("d")?.to_lowercase();
+    let selector = tag("s")?.clone();
+    let signed_fields: Vec<&str> = tag("h")?.split(':').collect();
+    if !signed_fields.iter().any(|name| name.eq_ignore_ascii_case("From")) {
+        return Err(invalid("the From field is not signed"));
+    }
+    let from = message.header("From").as_deref().and_then(address_domain);
+    if !from.is_some_and(|from| is_aligned(&from, &domain)) {
+        return Err(invalid("the From domain is not the signing domain"));
+    }
+
+    let body_hash = BASE64.decode(tag("bh")?).map_err(|_| invalid("malformed bh= tag"))?;
+    if Sha256::digest(canonical_body(message.body, relaxed_body))[..] != body_hash[..] {
+        return Err(invalid("the body was altered"));
+    }
+
+    let mut data = Vec::new();
+    let mut taken: BTreeMap<String, usize> = BTreeMap::new();
+    for name in signed_fields {
+        // repeated names select the instances of the field from the last one upwards, names
+        // without an instance left sign its absence
+        let count = taken.entry(name.to_lowercase()).or_default();
+        let field = message
+            .headers
+            .iter()
+            .rev()
+            .filter(|header| header.name.eq_ignore_ascii_case(name))
+            .nth(*count);
+        *count += 1;
+        if let Some(field) = field {
+            data.extend(canonical_field(field.raw, relaxed_headers));
+            data.extend_from_slice(b"\r\n");
+        }
+    }
+    data.extend(canonical_field(&without_signature(signature.raw), relaxed_headers));
+
+    let signature = BASE64.decode(tag("b")?).map_err(|_| invalid("malformed b= tag"))?;
+    public_key
+        .verify(Pkcs1v15Sign::new::<Sha256>(), &Sha256::digest(&data), &signature)
+        .map_err(|_| invalid("the signature does not match the key"))?;
+    Ok(Signer { domain, selector })
+}
+
+// The tags of a signature or key record, with the folding whitespace removed from their values
+fn tags(value: &str) -> BTreeMap<&str, String> {
+    value
+        .split(';')
+        .filter_map(|tag| tag.split_once('='))
+        .map(|(name, value)| (name.trim(), value.split_whitespace().collect()))
+        .collect()
+}
+
+// The domain of the address in a From field, e.g. `Fatura <faturas@edp.pt>`
+fn address_domain(from: &str) -> Option<String> {
+    let address = match from.rsplit_once('<') {
+        Some((_, rest)) => rest.split('>').next()?,
+        None => from,
+    };
+    let (_, domain) = address.rsplit_once('@')?;
+    Some(domain.trim().to_lowercase())
+}
+
+fn is_aligned(from: &str, signer: &str) -> bool {
+    from == signer || from.strip_suffix(signer).is_some_and(|sub| sub.ends_with('.'))
+}
+
+fn is_relaxed(canonicalization: &str) -> Result<bool, ResidencyError> {
+    match canonicalization {
+        "simple" => Ok(false),
+        "relaxed" => Ok(true),
+        _ => Err(ResidencyError::InvalidDkimSignature(
+            "unsupported canonicalization".to_string(),
+        )),
+    }
+}
+
+// The field as the signature covers it, without its final line break
+fn canonical_field(raw: &[u8], relaxed: bool) -> Vec<u8> {
+    if !relaxed {
+        return with_crlf(raw);
+    }
+    let colon = raw.iter().position(|&b| b == b':').unwrap_or(raw.len());
+    let (name, value) = raw.split_at(colon);
+    let value: Vec<u8> = value
+        .iter()
+        .skip(1)
+        .copied()
+        .filter(|&b| b != b'\r' && b != b'\n')
+        .collect();
+
+    let mut field = name.trim_ascii().to_ascii_lowercase();
+    field.push(b':');
+    field.extend_from_slice(compress_whitespace(&value).trim_ascii());
+    field
+}
+
+// The body as the signature covers it: every line ends with CRLF, trailing empty lines dropped
+fn canonical_body(body: &[u8], relaxed: bool) -> Vec<u8> {
+    let mut lines: Vec<Vec<u8>> = lines(body)
+        .map(|(_, line, _)| {
+            if relaxed {
+                compress_whitespace(line).trim_ascii_end().to_vec()
+            } else {
+                line.to_vec()
+            }
+        })
+        .collect();
+    while lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    // an empty body is a single line break in the simple canonicalization only
+    if lines.is_empty() && !relaxed {
+        return b"\r\n".to_vec();
+    }
+    lines
+        .into_iter()
+        .flat_map(|line| line.into_iter().chain(*b"\r\n"))
+        .collect()
+}
+
+// The DKIM-Signature field with the value of its b= tag emptied, as it was when it was signed
+fn without_signature(raw: &[u8]) -> Vec<u8> {
+    let colon = raw.iter().position(|&b| b == b':').unwrap_or(raw.len());
+    let mut field = raw[..colon].to_vec();
+    for (i, tag) in raw[colon..].split(|&b| b == b';').enumerate() {
+        if i > 0 {
+            field.push(b';');
+        }
+        match tag.iter().position(|&b| b == b'=') {
+            Some(equals) if i > 0 && tag[..equals].trim_ascii() == b"b" => {
+                field.extend_from_slice(&tag[..=equals]);
+            }
+            _ => field.extend_from_slice(tag),
+        }
+    }
+    field
+}
+
+// Runs of spaces and tabs become a single space
+fn compress_whitespace(data: &[u8]) -> Vec<u8> {
+    let mut compressed: Vec<u8> = Vec::with_capacity(data.len());
+    for &b in data {
+        let whitespace = b == b' ' || b == b'\t';
+        if !(whitespace && compressed.last() == Some(&b' ')) {
+            compressed.push(if whitespace { b' ' } else { b });
+        }
+    }
+    compressed
+}
+
+// Messages saved with bare line feeds were sent with CRLF, which is what was signed
+fn with_crlf(data: &[u8]) -> Vec<u8> {
+    let mut converted = Vec::with_capacity(data.len());
+    for (i, &b) in data.iter().enumerate() {
+        if b == b'\n' && (i == 0 || data[i - 1] != b'\r') {
+            converted.push(b'\r');
+        }
+        converted.push(b);
+    }
+    converted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_key() -> DkimKey {
+        include_str!("../../../examples/dkim_test_key.txt").parse().unwrap()
+    }
+
+    #[test]
+    fn canonicalizes_like_the_rfc_example() {
+        // RFC 6376, section 3.4.6
+        assert_eq!(canonical_field(b"A: X", true), b"a:X");
+        assert_eq!(canonical_field(b"B : Y\t\r\n\tZ  ", true), b"b:Y Z");
+        assert_eq!(canonical_field(b"B : Y\t\n\tZ  ", false), b"B : Y\t\r\n\tZ  ");
+
+        let body = b" C \r\nD \t E\r\n\r\n\r\n";
+        assert_eq!(canonical_body(body, true), b" C\r\nD E\r\n");
+        assert_eq!(canonical_body(body, false), b" C \r\nD \t E\r\n");
+        assert_eq!(canonical_body(b"", true), b"");
+        assert_eq!(canonical_body(b"", false), b"\r\n");
+
+        assert_eq!(
+            without_signature(b"DKIM-Signature: v=1; bh=abc;\r\n b=de\r\n f; s=x"),
+            b"DKIM-Signature: v=1; bh=abc;\r\n b=; s=x"
+        );
+    }
+
+    #[test]
+    fn aligns_sender_with_signer() {
+        let from = address_domain("Fatura =?utf-8?q?Eletr=C3=B3nica?= <faturas@Exemplo-Energia.pt>");
+        assert_eq!(from.as_deref(), Some("exemplo-energia.pt"));
+        assert_eq!(address_domain("faturas@edp.pt").as_deref(), Some("edp.pt"));
+        assert_eq!(address_domain("Fatura"), None);
+
+        assert!(is_aligned("edp.pt", "edp.pt"));
+        assert!(is_aligned("faturas.edp.pt", "edp.pt"));
+        assert!(!is_aligned("notedp.pt", "edp.pt"));
+        assert!(!is_aligned("edp.pt", "faturas.edp.pt"));
+    }
+
+    #[test]
+    fn verifies_signed_email() {
+        let email = include_bytes!("../../../examples/sample_invoice_dkim.eml");
+        let signer = Signer {
+            domain: "exemplo-energia.pt".to_string(),
+            selector: "fatura2024".to_string(),
+        };
+        assert_eq!(verify(email, &test_key()), Ok(signer));
+
+        let other_key = DkimKey::new(test_key().public_key[..200].to_vec());
+        assert!(matches!(
+            verify(email, &other_key),
+            Err(ResidencyError::InvalidDkimSignature(_))
+        ));
+
+        let unsigned = include_bytes!("../../../examples/sample_invoice.eml");
+        assert_eq!(verify(unsigned, &test_key()), Err(ResidencyError::NoDkimSignature));
+        let pdf = include_bytes!("../../../examples/sample_invoice.pdf");
+        assert_eq!(verify(pdf, &test_key()), Err(ResidencyError::NoDkimSignature));
+    }
+
+    #[test]
+    fn rejects_altered_email() {
+        let email = include_str!("../../../examples/sample_invoice_dkim.eml");
+        for (original, altered) in [
+            ("Maria Santos <maria", "Mario Santos <maria"),
+            ("no valor de 61,50", "no valor de 16,50"),
+            ("b=WSzNPrrheZc7", "b=WSzNPrrheZc8"),
+        ] {
+            let altered = email.replacen(original, altered, 1);
+            assert!(matches!(
+                verify(altered.as_bytes(), &test_key()),
+                Err(ResidencyError::InvalidDkimSignature(_))
+            ));
+        }
+        let other_sender = email.replacen("faturas@exemplo-energia.pt", "faturas@outro.pt", 1);
+        assert_eq!(
+            verify(other_sender.as_bytes(), &test_key()),
+            Err(ResidencyError::InvalidDkimSignature(
+                "the From domain is not the signing domain".to_string()
+            ))
+        );
+        // relaxed header canonicalization tolerates refolding, the simple body one does not
+        let refolded = email.replacen("Subject: ", "Subject:   ", 1);
+        assert!(verify(refolded.as_bytes(), &test_key()).is_ok());
+    }
+
+    #[test]
+    fn parses_key_records() {
+        let key = test_key();
+        let record = include_str!("../../../examples/dkim_test_key.txt");
+        let bare: DkimKey = record.trim().trim_start_matches("v=DKIM1; k=rsa; p=").parse().unwrap();
+        assert_eq!(bare, key);
+        assert!(key.rsa_public_key().is_some());
+        assert!("v=DKIM1; k=ed25519; p=abcd".parse::<DkimKey>().is_err());
+        assert!("v=DKIM1; k=rsa; p=".parse::<DkimKey>().is_err());
+    }
+}
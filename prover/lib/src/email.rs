@@ -0,0 +1,326 @@
+// prover/lib/src/email.rs
+//! Bills as they arrive by email: attached to a MIME message (RFC 5322 and RFC 2045), or encoded
+//! in base64.
+use crate::ResidencyError;
+use base64::engine::general_purpose::{GeneralPurpose, GeneralPurposeConfig};
+use base64::engine::{DecodePaddingMode, Engine};
+use base64::alphabet;
+use std::borrow::Cow;
+
+/// Decodes base64 with or without its trailing padding, as mail clients write both.
+pub(crate) const BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// The most levels of nested multiparts and attached messages searched for the bill.
+const MAX_DEPTH: usize = 8;
+
+/// Returns the PDF in `input`: the input itself if it is a PDF, the decoded bytes if it is a
+/// PDF encoded in base64, or the first PDF attached to it if it is an email.
+///
+/// Inputs that are none of these are returned as they are, for the PDF parser to reject.
+pub(crate) fn pdf_document(input: &[u8]) -> Result<Cow<'_, [u8]>, ResidencyError> {
+    if is_pdf(input) {
+        return Ok(Cow::Borrowed(input));
+    }
+    if let Some(pdf) = decode_base64_pdf(input) {
+        return Ok(Cow::Owned(pdf));
+    }
+    match Part::parse(input).filter(Part::is_message) {
+        Some(message) => find_pdf(&message, 0)
+            .map(Cow::Owned)
+            .ok_or(ResidencyError::NoPdfAttachment),
+        None => Ok(Cow::Borrowed(input)),
+    }
+}
+
+/// A MIME entity: a message, or one of the parts of a multipart body.
+pub(crate) struct Part<'a> {
+    pub(crate) headers: Vec<Header<'a>>,
+    /// The body as it appears in the message, still encoded.
+    pub(crate) body: &'a [u8],
+}
+
+/// A header field of a [`Part`].
+pub(crate) struct Header<'a> {
+    /// The field as it appears in the message, with its folded lines and without the final line
+    /// break.
+    pub(crate) raw: &'a [u8],
+    pub(crate) name: &'a str,
+}
+
+impl Header<'_> {
+    /// The value of the field, unfolded and trimmed.
+    pub(crate) fn value(&self) -> String {
+        let value = &self.raw[self.name.len() + 1..];
+        String::from_utf8_lossy(value)
+            .replace(['\r', '\n'], "")
+            .trim()
+            .to_string()
+    }
+}
+
+impl<'a> Part<'a> {
+    /// Splits the header fields from the body, at the first empty line. Returns `None` if a line
+    /// before it is not a header field.
+    pub(crate) fn parse(input: &'a [u8]) -> Option<Part<'a>> {
+        let mut headers: Vec<Header> = Vec::new();
+        let mut start = 0;
+        for (line_start, line, next) in lines(input) {
+            if line.is_empty() {
+                return Some(Part {
+                    headers,
+                    body: &input[next..],
+                });
+            }
+            // a line starting with whitespace continues the previous field
+            if line[0] == b' ' || line[0] == b'\t' {
+                let header = headers.last_mut()?;
+                header.raw = &input[start..line_start + line.len()];
+                continue;
+            }
+
+            let colon = line.iter().position(|&b| b == b':')?;
+            let name = std::str::from_utf8(&line[..colon]).ok()?;
+            if name.is_empty() || !name.bytes().all(|b| b.is_ascii_graphic()) {
+                return None;
+            }
+            start = line_start;
+            headers.push(Header { raw: line, name });
+        }
+        // a message without a body
+        (!headers.is_empty()).then_some(Part {
+            headers,
+            body: &input[input.len()..],
+        })
+    }
+
+    /// The value of the last instance of the field, the one a DKIM signature covers first: a
+    /// field prepended above it would otherwise be read instead of the signed one.
+    pub(crate) fn header(&self, name: &str) -> Option<String> {
+        self.headers
+            .iter()
+            .rev()
+            .find(|header| header.name.eq_ignore_ascii_case(name))
+            .map(Header::value)
+    }
+
+    fn is_message(&self) -> bool {
+        ["From", "MIME-Version", "Content-Type"]
+            .iter()
+            .any(|name| self.header(name).is_some())
+    }
+
+    /// The media type, lowercase, and the value of the `Content-Type` field. Parts are plain text
+    /// unless they say otherwise.
+    fn content_type(&self) -> (String, String) {
+        let value = self
+            .header("Content-Type")
+            .unwrap_or_else(|| "text/plain".to_string());
+        let media_type = value.split(';').next().unwrap_or_default().trim().to_lowercase();
+        (media_type, value)
+    }
+
+    /// The body, decoded from its `Content-Transfer-Encoding`.
+    fn decoded_body(&self) -> Option<Vec<u8>> {
+        let encoding = self.header("Content-Transfer-Encoding").unwrap_or_default();
+        match encoding.to_lowercase().as_str() {
+            "base64" => {
+                let data: Vec<u8> = self
+                    .body
+                    .iter()
+                    .copied()
+                    .filter(|b| !b.is_ascii_whitespace())
+                    .collect();
+                BASE64.decode(data).ok()
+            }
+            "quoted-printable" => Some(decode_quoted_printable(self.body)),
+            _ => Some(self.body.to_vec()),
+        }
+    }
+}
+
+// Searches the part and the parts nested in it for the first PDF
+fn find_pdf(part: &Part, depth: usize) -> Option<Vec<u8>> {
+    if depth > MAX_DEPTH {
+        return None;
+    }
+
+    let (media_type, content_type) = part.content_type();
+    if media_type.starts_with("multipart/") {
+        let boundary = parameter(&content_type, "boundary")?;
+        multipart_bodies(part.body, &boundary)
+            .into_iter()
+            .filter_map(Part::parse)
+            .find_map(|part| find_pdf(&part, depth + 1))
+    } else if media_type == "message/rfc822" {
+        let message = part.decoded_body()?;
+        find_pdf(&Part::parse(&message)?, depth + 1)
+    } else {
+        // the declared type is often application/octet-stream, the content tells a PDF apart
+        part.decoded_body().filter(|body| is_pdf(body))
+    }
+}
+
+// The bodies of the parts of a multipart body, without the line break before each delimiter
+fn multipart_bodies<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{}", boundary);
+    let mut bodies = Vec::new();
+    let mut part_start = None;
+
+    for (line_start, line, next) in lines(body) {
+        let Some(rest) = line.strip_prefix(delimiter.as_bytes()) else {
+            continue;
+        };
+        let closing = rest.starts_with(b"--");
+        if !closing && !rest.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+
+        if let Some(start) = part_start {
+            let end = if body[..line_start].ends_with(b"\r\n") {
+                line_start - 2
+            } else {
+                line_start.saturating_sub(1)
+            };
+            bodies.push(&body[start..end.max(start)]);
+        }
+        if closing {
+            break;
+        }
+        part_start = Some(next);
+    }
+    bodies
+}
+
+// The value of a parameter of a header field, e.g. the boundary of `multipart/mixed;
+// boundary="abc"`
+fn parameter(value: &str, name: &str) -> Option<String> {
+    value.split(';').skip(1).find_map(|parameter| {
+        let (key, value) = parameter.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+fn decode_quoted_printable(body: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(body.len());
+    let mut i = 0;
+    while i < body.len() {
+        if body[i] != b'=' {
+            decoded.push(body[i]);
+            i += 1;
+            continue;
+        }
+        // a soft line break, or an escaped byte
+        let rest = &body[i + 1..];
+        if rest.starts_with(b"\r\n") {
+            i += 3;
+        } else if rest.starts_with(b"\n") {
+            i += 2;
+        } else if let Some(byte) = rest
+            .get(..2)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(b'=');
+            i += 1;
+        }
+    }
+    decoded
+}
+
+fn decode_base64_pdf(input: &[u8]) -> Option<Vec<u8>> {
+    let input = std::str::from_utf8(input).ok()?.trim();
+    // e.g. a data URL from a browser upload
+    let data = match input.split_once(";base64,") {
+        Some((prefix, data)) if prefix.starts_with("data:") => data,
+        _ => input,
+    };
+    let data: String = data.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    BASE64.decode(data).ok().filter(|pdf| is_pdf(pdf))
+}
+
+// PDF readers accept the header anywhere in the first kilobyte of the file
+fn is_pdf(data: &[u8]) -> bool {
+    data[..data.len().min(1024)]
+        .windows(5)
+        .any(|window| window == b"%PDF-")
+}
+
+// The lines of `input` as (start offset, content without the line break, offset of the next line)
+pub(crate) fn lines(input: &[u8]) -> impl Iterator<Item = (usize, &[u8], usize)> {
+    let mut start = 0;
+    std::iter::from_fn(move || {
+        if start >= input.len() {
+            return None;
+        }
+        let line_start = start;
+        let (end, next) = match input[start..].iter().position(|&b| b == b'\n') {
+            Some(newline) => (start + newline, start + newline + 1),
+            None => (input.len(), input.len()),
+        };
+        let line = input[start..end].strip_suffix(b"\r").unwrap_or(&input[start..end]);
+        start = next;
+        Some((line_start, line, next))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PDF: &[u8] = b"%PDF-1.4\nnot much of a bill\n%%EOF\n";
+
+    #[test]
+    fn finds_pdf_in_nested_multiparts() {
+        let message = format!(
+            "From: faturas@exemplo-energia.pt\r\n\
+             Content-Type: multipart/mixed;\r\n boundary=\"outer\"\r\n\r\n\
+             preamble\r\n\
+             --outer\r\n\
+             Content-Type: text/plain\r\n\r\n\
+             Segue a fatura.\r\n\
+             --outer\r\n\
+             Content-Type: message/rfc822\r\n\r\n\
+             Content-Type: multipart/mixed; boundary=inner\r\n\r\n\
+             --inner\r\n\
+             Content-Type: application/octet-stream\r\n\
+             Content-Transfer-Encoding: base64\r\n\r\n\
+             {}\r\n\
+             --inner--\r\n\
+             --outer--\r\n",
+            BASE64.encode(PDF)
+        );
+        assert_eq!(pdf_document(message.as_bytes()).unwrap().as_ref(), PDF);
+
+        // a Content-Type prepended to the signed one is not the one read
+        let prepended = format!("Content-Type: text/plain\r\n{}", message);
+        assert_eq!(pdf_document(prepended.as_bytes()).unwrap().as_ref(), PDF);
+
+        let without_pdf = "From: faturas@exemplo-energia.pt\n\nOla\n";
+        assert_eq!(
+            pdf_document(without_pdf.as_bytes()),
+            Err(ResidencyError::NoPdfAttachment)
+        );
+    }
+
+    #[test]
+    fn decodes_transfer_encodings() {
+        assert_eq!(
+            decode_quoted_printable(b"Ol=C3=A1 Maria, uma linha =\r\nlonga = fim"),
+            "Olá Maria, uma linha longa = fim".as_bytes()
+        );
+
+        let encoded = BASE64.encode(PDF);
+        assert_eq!(pdf_document(encoded.as_bytes()).unwrap().as_ref(), PDF);
+        let data_url = format!("data:application/pdf;base64,{}", encoded.trim_end_matches('='));
+        assert_eq!(pdf_document(data_url.as_bytes()).unwrap().as_ref(), PDF);
+        assert_eq!(pdf_document(b"not a pdf").unwrap().as_ref(), b"not a pdf");
+    }
+}
@@ -0,0 +1,156 @@
+// prover/lib/src/holder.rs
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// How the claimant is named on their bills.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HolderId {
+    /// The full name, matched regardless of case and spacing.
+    Name(String),
+    /// The tax number, without country prefix.
+    TaxId(String),
+}
+
+impl HolderId {
+    /// Whether the bill names the claimant as the value of one of `labels`, e.g. `Cliente:`,
+    /// printed after the label on its line, or on the next line if the label is alone.
+    ///
+    /// Names match as whole words, tax numbers as a whole token, optionally prefixed with a
+    /// two-letter country code. Elsewhere in the text, they may be anyone's: a name in a
+    /// greeting, a number that happens to have the same digits.
+    pub fn appears_in(&self, text: &str, labels: &[&str]) -> bool {
+        labelled_values(text, labels).into_iter().any(|value| {
+            let value = normalize(value);
+            match self {
+                HolderId::Name(name) => {
+                    let name = normalize(name);
+                    !name.is_empty()
+                        && value.match_indices(&name).any(|(i, _)| {
+                            let before = value[..i].chars().next_back();
+                            let after = value[i + name.len()..].chars().next();
+                            !before.is_some_and(char::is_alphanumeric)
+                                && !after.is_some_and(char::is_alphanumeric)
+                        })
+                }
+                HolderId::TaxId(tax_id) => {
+                    let tax_id = normalize(tax_id).replace(' ', "");
+                    !tax_id.is_empty()
+                        && value
+                            .replace(' ', "")
+                            .split(|c: char| !c.is_alphanumeric())
+                            .any(|token| {
+                                token.strip_suffix(tax_id.as_str()).is_some_and(|prefix| {
+                                    prefix.is_empty()
+                                        || prefix.len() == 2
+                                            && prefix.chars().all(|c| c.is_ascii_alphabetic())
+                                })
+                            })
+                }
+            }
+        })
+    }
+
+    fn canonical(&self) -> String {
+        match self {
+            HolderId::Name(name) => format!("name:{}", normalize(name)),
+            HolderId::TaxId(tax_id) => format!("tax_id:{}", normalize(tax_id).replace(' ', "")),
+        }
+    }
+}
+
+/// The claimant of a residency proof, a private input that never leaves the prover.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Holder {
+    pub id: HolderId,
+    /// A random secret kept by the claimant, so the commitment cannot be brute-forced from a list
+    /// of names or tax numbers.
+    pub salt: [u8; 32],
+}
+
+impl Holder {
+    /// The hex-encoded SHA-256 of the salt followed by the normalized identity.
+    pub fn commitment(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.salt);
+        hasher.update(self.id.canonical().as_bytes());
+        hex::encode(hasher.finalize())
+    }
+}
+
+// The text printed after every instance of one of `labels`, up to the next column of its line
+fn labelled_values<'a>(text: &'a str, labels: &[&str]) -> Vec<&'a str> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut values = Vec::new();
+    for (row, line) in lines.iter().enumerate() {
+        for label in labels {
+            let re = Regex::new(&format!(r"(?i)\b{}\b", regex::escape(label))).unwrap();
+            for found in re.find_iter(line) {
+                let rest = line[found.end()..].trim_start_matches([':', '.', ' ']);
+                let value = match rest {
+                    "" => lines[row + 1..].iter().map(|line| line.trim()).find(|line| !line.is_empty()),
+                    rest => Some(rest),
+                };
+                values.extend(value.and_then(|value| value.split("  ").next()));
+            }
+        }
+    }
+    values
+}
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LABELS: &[&str] = &["Cliente", "NIF do cliente"];
+
+    #[test]
+    fn matches_names_as_whole_words() {
+        let text = "Cliente: MARIA Santos  Data: 07/12/2024\nRua de Exemplo, 123";
+        assert!(HolderId::Name("Maria Santos".to_string()).appears_in(text, LABELS));
+        assert!(!HolderId::Name("Maria San".to_string()).appears_in(text, LABELS));
+        assert!(!HolderId::Name(" ".to_string()).appears_in(text, LABELS));
+        assert!(HolderId::Name("Maria Santos".to_string()).appears_in("CLIENTE\n\nMaria Santos", LABELS));
+    }
+
+    #[test]
+    fn matches_holder_after_a_customer_label_only() {
+        let nif = |nif: &str| HolderId::TaxId(nif.to_string());
+        assert!(nif("123456789").appears_in("NIF do cliente: 123 456 789", LABELS));
+        assert!(nif("123456789").appears_in("NIF do cliente: PT123456789", LABELS));
+        // any run of digits elsewhere, or the issuer's own number, is not the customer's
+        assert!(!nif("123456789").appears_in("Referência: 1234567890\nNIF: 123456789", LABELS));
+        assert!(!nif("123456789").appears_in("NIF do cliente: 501234560  NIF: 123456789", LABELS));
+        assert!(!nif("23456789").appears_in("NIF do cliente: 123456789", LABELS));
+        let name = HolderId::Name("Maria Santos".to_string());
+        assert!(!name.appears_in("Cara Maria Santos, segue a fatura.", LABELS));
+        assert!(!name.appears_in("Nº Cliente: 4471\nMaria Santos", LABELS));
+        assert!(!name.appears_in("Clientes Maria Santos", LABELS));
+    }
+
+    #[test]
+    fn commitment_depends_on_salt_and_identity() {
+        let holder = Holder {
+            id: HolderId::Name("Maria Santos".to_string()),
+            salt: [7; 32],
+        };
+        let same = Holder {
+            id: HolderId::Name(" maria  SANTOS".to_string()),
+            ..holder.clone()
+        };
+        let salted = Holder {
+            salt: [8; 32],
+            ..holder.clone()
+        };
+
+        assert_eq!(holder.commitment(), same.commitment());
+        assert_ne!(holder.commitment(), salted.commitment());
+    }
+}
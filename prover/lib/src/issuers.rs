@@ -0,0 +1,78 @@
+// prover/lib/src/issuers.rs
+use crate::{Country, KnownIssuer, Registry};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+
+/// The tax numbers of the companies and public bodies whose bills are accepted.
+///
+/// Proofs commit [`TrustedIssuers::commitment`] rather than the list itself, so a verifier can
+/// tell which policy a proof was generated under by hashing the list it expects.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrustedIssuers {
+    tax_ids: BTreeSet<String>,
+}
+
+impl TrustedIssuers {
+    pub fn new<I, S>(tax_ids: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            tax_ids: tax_ids.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// The issuers known to the validator of `country` in the default [`Registry`].
+    pub fn known(country: Country) -> Self {
+        let registry = Registry::default();
+        let known = registry.get(country).map_or(&[][..], |v| v.known_issuers());
+        Self::from(known)
+    }
+
+    pub fn contains(&self, tax_id: &str) -> bool {
+        self.tax_ids.contains(tax_id)
+    }
+
+    /// The hex-encoded SHA-256 of the sorted tax numbers, each followed by a newline.
+    pub fn commitment(&self) -> String {
+        let mut hasher = Sha256::new();
+        for tax_id in &self.tax_ids {
+            hasher.update(tax_id.as_bytes());
+            hasher.update(b"\n");
+        }
+        hex::encode(hasher.finalize())
+    }
+}
+
+impl From<&[KnownIssuer]> for TrustedIssuers {
+    fn from(issuers: &[KnownIssuer]) -> Self {
+        Self::new(issuers.iter().map(|issuer| issuer.tax_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commitment_ignores_order_and_duplicates() {
+        let a = TrustedIssuers::new(["503504564", "500906840"]);
+        let b = TrustedIssuers::new(["500906840", "503504564", "500906840"]);
+        assert_eq!(a.commitment(), b.commitment());
+        assert_ne!(a.commitment(), TrustedIssuers::new(["503504564"]).commitment());
+        assert_eq!(
+            a.commitment(),
+            "0e223cb8a9c5a424ecd97a1d8092da047a50f771bd1b1463b177049cf8327b6f"
+        );
+    }
+
+    #[test]
+    fn known_issuers_of_a_country() {
+        let trusted = TrustedIssuers::known(Country::Portugal);
+        assert!(trusted.contains("503504564"));
+        assert!(!trusted.contains("501234560"));
+        assert_eq!(TrustedIssuers::known(Country::Spain), TrustedIssuers::default());
+    }
+}
@@ -1,46 +1,812 @@
 // prover/lib/src/lib.rs
-mod portugal;
+mod address;
+mod claim;
+mod date;
+mod disclosure;
+mod dkim;
+mod email;
+mod holder;
+mod issuers;
+mod nullifier;
+mod pdf;
+pub mod portugal;
+pub mod qr;
+mod signature;
+pub mod spain;
+pub mod text;
+mod validator;
 
+pub use address::Address;
+pub use claim::{Residency, ResidencyClaim, ResidencyError};
+pub use date::Date;
+pub use disclosure::{Disclosed, Disclosure};
+pub use dkim::DkimKey;
+pub use holder::{Holder, HolderId};
+pub use issuers::TrustedIssuers;
+pub use nullifier::nullifier;
+pub use signature::TrustedRoots;
+pub use validator::{CountryValidator, ExtractionHints, IssuerCategory, KnownIssuer, Registry};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Scope {
     Country,
+    /// A district, or one of the autonomous regions.
+    Region,
     City,
 }
 
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "country" => Ok(Scope::Country),
+            "region" | "district" => Ok(Scope::Region),
+            "city" => Ok(Scope::City),
+            _ => Err(format!("Unknown scope: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Country {
     Portugal,
+    Spain,
 }
 
+impl Country {
+    /// The ISO 3166-1 alpha-2 code of the country.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Country::Portugal => "PT",
+            Country::Spain => "ES",
+        }
+    }
+}
+
+impl FromStr for Country {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "portugal" | "pt" => Ok(Country::Portugal),
+            "spain" | "españa" | "es" => Ok(Country::Spain),
+            _ => Err(format!("Unknown country: {}", s)),
+        }
+    }
+}
+
+/// Rejects bills issued more than `max_age_days` before `today`.
+///
+/// The zkVM has no clock, so the current date is an input of the proof and is committed with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Freshness {
+    pub today: Date,
+    pub max_age_days: u32,
+}
+
+/// How long a residency claim stays valid by default, counted from the end of the month the bill
+/// was issued in.
+pub const DEFAULT_VALIDITY_DAYS: u32 = 365;
+
+/// The longest validity window a claim can be made with, so that a bill cannot vouch for a
+/// residency years after it was issued.
+pub const MAX_VALIDITY_DAYS: u32 = 730;
+
 pub struct Config {
     pub scope: Scope,
     pub country: Country,
+    /// Only accept bills from these issuers, any valid issuer is accepted if `None`.
+    pub trusted_issuers: Option<TrustedIssuers>,
+    /// Only accept recent bills, bills of any date are accepted if `None`.
+    pub freshness: Option<Freshness>,
+    /// The claimant the bill must be addressed to, the proof is not bound to anyone if `None`.
+    pub holder: Option<Holder>,
+    /// Separates the nullifiers of the applications that accept residency proofs, see
+    /// [`nullifier`].
+    pub nullifier_domain: String,
+    /// The number of days after the end of the month it was issued in the bill proves residency
+    /// for, up to the end of that month, see [`Residency::expiry`]. At most
+    /// [`MAX_VALIDITY_DAYS`].
+    pub validity_days: u32,
+    /// A secret salt to commit the normalized address with, see [`Address::commitment`]. The
+    /// address is not committed if `None`.
+    pub address_salt: Option<[u8; 32]>,
+    /// The attributes the claim reveals, the ones of [`Disclosure::for_scope`] if `None`.
+    pub disclosure: Option<Disclosure>,
+    /// Requires the bills to span at least this many months between the oldest and the newest,
+    /// all at the same address, see [`ResidencyClaim::resident_since_at_least`].
+    pub min_residency_months: Option<u32>,
+    /// Only accept bills digitally signed with a certificate that chains up to one of these
+    /// roots, unsigned bills are accepted if `None`.
+    pub trusted_roots: Option<TrustedRoots>,
+    /// Only accept bills attached to an email DKIM-signed with this key, bills are accepted in
+    /// any form if `None`.
+    pub dkim_key: Option<DkimKey>,
+}
+
+impl Default for Config {
+    /// Proves the country of residence in Portugal, without any of the optional checks.
+    fn default() -> Self {
+        Self {
+            scope: Scope::Country,
+            country: Country::Portugal,
+            trusted_issuers: None,
+            freshness: None,
+            holder: None,
+            nullifier_domain: "proof-of-residency".to_string(),
+            validity_days: DEFAULT_VALIDITY_DAYS,
+            address_salt: None,
+            disclosure: None,
+            min_residency_months: None,
+            trusted_roots: None,
+            dkim_key: None,
+        }
+    }
 }
 
-pub fn run(pdf_bytes: &[u8], config: Config) -> bool {
-    use pdf_extract;
-    use portugal;
-    // TODO: If the file is encoded in base64, decode it
+/// Validates a bill and extracts the residency it proves.
+///
+/// `pdf_bytes` is the PDF of the bill, the PDF encoded in base64, or the email (`.eml`) the bill
+/// is attached to.
+pub fn run(pdf_bytes: &[u8], config: Config) -> Result<Residency, ResidencyError> {
+    run_with(&Registry::default(), pdf_bytes, config)
+}
+
+/// Same as [`run`], with the country validators taken from `registry`.
+pub fn run_with(
+    registry: &Registry,
+    pdf_bytes: &[u8],
+    config: Config,
+) -> Result<Residency, ResidencyError> {
+    run_all_with(registry, &[pdf_bytes], config)
+}
+
+/// Same as [`run`], for several bills that must all prove the same residency: each one is
+/// validated on its own, then they must come from distinct issuers and be addressed to the same
+/// postal code. The bills are combined into one [`Residency`], see [`Residency::combine`].
+pub fn run_all<T: AsRef<[u8]>>(documents: &[T], config: Config) -> Result<Residency, ResidencyError> {
+    run_all_with(&Registry::default(), documents, config)
+}
+
+/// Same as [`run_all`], with the country validators taken from `registry`.
+pub fn run_all_with<T: AsRef<[u8]>>(
+    registry: &Registry,
+    documents: &[T],
+    config: Config,
+) -> Result<Residency, ResidencyError> {
+    let validator = registry
+        .get(config.country)
+        .ok_or(ResidencyError::UnsupportedCountry(config.country))?;
+    if config.validity_days > MAX_VALIDITY_DAYS {
+        return Err(ResidencyError::ValidityTooLong);
+    }
+
+    let residencies = documents
+        .iter()
+        .map(|pdf_bytes| validate(validator, pdf_bytes.as_ref(), &config))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let months = config.min_residency_months;
+    // the period only counts if every bill shows the full address, not just the postal code
+    if months.is_some() && residencies.iter().any(|r| r.normalized_address.is_none()) {
+        return Err(ResidencyError::NoAddress);
+    }
+    let newest_expiry = residencies.iter().max_by_key(|r| r.issue_date).map(|r| r.expiry);
+    let mut residency = Residency::combine(residencies)?;
+    if let Some(months) = months {
+        if residency.first_issue_date.months_until(residency.issue_date) < i64::from(months) {
+            return Err(ResidencyError::ResidencyTooShort);
+        }
+        residency.resident_since_at_least = Some(months);
+        // the oldest bill only proves when the period started: its expiry would give away its
+        // date, and with the issue month of the newest bill the span the claim keeps hidden
+        residency.expiry = newest_expiry.unwrap_or(residency.expiry);
+    }
+    // the expiry revealed is the one of the combined claim
+    if residency.disclosed.expiry.is_some() {
+        residency.disclosed.expiry = Some(residency.expiry);
+    }
+    Ok(residency)
+}
+
+// Validates a single bill
+fn validate(
+    validator: &dyn CountryValidator,
+    pdf_bytes: &[u8],
+    config: &Config,
+) -> Result<Residency, ResidencyError> {
+    // the signature covers the attachment, so the bill is the one the domain sent
+    let dkim_signer = match &config.dkim_key {
+        Some(key) => Some(dkim::verify(pdf_bytes, key)?),
+        None => None,
+    };
+
+    // the bill may be encoded in base64, or attached to the email it was delivered with
+    let pdf_bytes: &[u8] = &email::pdf_document(pdf_bytes)?;
+
+    // from the bytes of the file, extract the text, in the same form whatever software wrote it
+    let document = pdf::load(pdf_bytes)?;
+    let pdf = text::normalize(&pdf::text(&document)?);
+
+    let hints = validator.extraction_hints();
+    if !hints.markers.iter().any(|marker| pdf.contains(marker)) {
+        return Err(ResidencyError::UnrecognizedDocument);
+    }
+
+    let document_id = validator
+        .document_id(&pdf)
+        .ok_or(ResidencyError::NoDocumentId)?;
+
+    let qr_codes: Vec<String> = pdf::images(&document)?
+        .iter()
+        .filter_map(qr::decode)
+        .collect();
+    let qr_fields = validator.verify_qr_codes(&document_id, &qr_codes)?;
+
+    let issuer_tax_id = validator
+        .issuer_tax_id(&pdf, &qr_fields)
+        .ok_or(ResidencyError::NoIssuerTaxId)?;
+    if let Some(trusted_issuers) = &config.trusted_issuers {
+        if !trusted_issuers.contains(&issuer_tax_id) {
+            return Err(ResidencyError::UnknownIssuer);
+        }
+    }
 
-    // from the bytes of the file, extract the text
-    let pdf = pdf_extract::extract_text_from_mem(&pdf_bytes).unwrap();
+    let issue_date = validator
+        .issue_date(&pdf, &qr_fields)
+        .ok_or(ResidencyError::NoIssueDate)?;
+    if let Some(Freshness { today, max_age_days }) = config.freshness {
+        let age = issue_date.days_until(today);
+        if age < 0 {
+            return Err(ResidencyError::FutureIssueDate);
+        }
+        if age > i64::from(max_age_days) {
+            return Err(ResidencyError::StaleDocument);
+        }
+    }
 
-    println!(r#"{:#?}"#, pdf);
+    // the signing certificate must have been valid when the bill was issued, and be the issuer's
+    if let Some(trusted_roots) = &config.trusted_roots {
+        let organization = format!("VAT{}-{}", config.country.code(), issuer_tax_id);
+        signature::verify(pdf_bytes, trusted_roots, issue_date, &organization)?;
+    }
 
-    match config.country {
-        Country::Portugal => portugal::validate(pdf),
+    if let Some(holder) = &config.holder {
+        if !validator.is_customer(&pdf, &qr_fields, &holder.id) {
+            return Err(ResidencyError::HolderMismatch);
+        }
     }
+
+    let postal_code = validator
+        .postal_code(&pdf)
+        .ok_or(ResidencyError::NoPostalCode)?;
+    let location = validator.resolve_scope(&postal_code, config.scope)?;
+    let normalized_address = validator.address(&pdf);
+    let address = match &config.address_salt {
+        Some(salt) => Some(
+            normalized_address
+                .as_ref()
+                .ok_or(ResidencyError::NoAddress)?
+                .commitment(salt),
+        ),
+        None => None,
+    };
+
+    let disclosure = config
+        .disclosure
+        .unwrap_or_else(|| Disclosure::for_scope(config.scope));
+    // from the end of one month to the end of another, so that the expiry only depends on the
+    // month the bill was issued in, not its day
+    let expiry = issue_date
+        .end_of_month()
+        .add_days(i64::from(config.validity_days))
+        .end_of_month();
+    let disclosed = disclosure.disclose(
+        validator,
+        config.country,
+        &postal_code,
+        &issuer_tax_id,
+        issue_date,
+        expiry,
+    )?;
+
+    // the nullifier only depends on the bill, so proving another scope does not give a fresh one
+    let bill_nullifier = nullifier(&config.nullifier_domain, &document_id, &issuer_tax_id);
+
+    Ok(Residency {
+        document_id,
+        issuer_tax_id,
+        issue_date,
+        first_issue_date: issue_date,
+        expiry,
+        validity_days: config.validity_days,
+        postal_code,
+        normalized_address,
+        country: config.country,
+        scope: config.scope,
+        location,
+        disclosed,
+        disclosure: disclosure.commitment(),
+        qr_fields,
+        trusted_issuers: config.trusted_issuers.as_ref().map(TrustedIssuers::commitment),
+        trusted_roots: config.trusted_roots.as_ref().map(TrustedRoots::commitment),
+        dkim_key: config.dkim_key.as_ref().map(DkimKey::commitment),
+        dkim_domain: dkim_signer.as_ref().map(|signer| signer.domain.clone()),
+        dkim_selector: dkim_signer.map(|signer| signer.selector),
+        freshness: config.freshness,
+        holder: config.holder.as_ref().map(Holder::commitment),
+        address,
+        resident_since_at_least: None,
+        nullifier_domain: config.nullifier_domain.clone(),
+        nullifiers: vec![bill_nullifier],
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const SAMPLE_QR_CODE: &str = "A:501234560*B:123456789*C:PT*D:FT*E:N*F:20241207*G:FT 2024/001*H:ABC12345-0001*I1:PT*I7:50.00*I8:11.50*N:11.50*O:61.50*Q:abcd*R:9999";
+
     #[test]
     fn it_works() {
         let file_bytes = std::fs::read("../../examples/sample_invoice.pdf").unwrap();
         let result = run(&file_bytes, Config {
             scope: Scope::Country,
             country: Country::Portugal,
+            ..Default::default()
+        });
+        assert_eq!(result, Ok(Residency {
+            document_id: "ABC12345-0001".to_string(),
+            issuer_tax_id: "501234560".to_string(),
+            issue_date: Date::new(2024, 12, 7).unwrap(),
+            first_issue_date: Date::new(2024, 12, 7).unwrap(),
+            expiry: Date::new(2025, 12, 31).unwrap(),
+            validity_days: DEFAULT_VALIDITY_DAYS,
+            postal_code: "4050-123".to_string(),
+            normalized_address: portugal::extract_address("Rua de Exemplo, 123\n4050-123 Porto"),
+            country: Country::Portugal,
+            scope: Scope::Country,
+            location: "Portugal".to_string(),
+            disclosed: Disclosed {
+                country: Some(Country::Portugal),
+                issue_month: Some("2024-12".to_string()),
+                expiry: Some(Date::new(2025, 12, 31).unwrap()),
+                ..Default::default()
+            },
+            disclosure: Disclosure::for_scope(Scope::Country).commitment(),
+            qr_fields: portugal::FiscalQrCode::parse(SAMPLE_QR_CODE).unwrap().fields,
+            trusted_issuers: None,
+            trusted_roots: None,
+            dkim_key: None,
+            dkim_domain: None,
+            dkim_selector: None,
+            freshness: None,
+            holder: None,
+            address: None,
+            resident_since_at_least: None,
+            nullifier_domain: "proof-of-residency".to_string(),
+            nullifiers: vec![nullifier("proof-of-residency", "ABC12345-0001", "501234560")],
+        }));
+    }
+
+    #[test]
+    fn city_scope_discloses_municipality_only() {
+        let file_bytes = std::fs::read("../../examples/sample_invoice.pdf").unwrap();
+        let residency = run(&file_bytes, Config {
+            scope: Scope::City,
+            country: Country::Portugal,
+            ..Default::default()
+        }).unwrap();
+
+        let claim = serde_json::to_string(&residency.claim()).unwrap();
+        assert_eq!(residency.location, "Porto");
+        assert_eq!(residency.claim().disclosed.municipality, Some("Porto".to_string()));
+        assert!(!claim.contains(&residency.postal_code));
+    }
+
+    #[test]
+    fn region_scope_discloses_district() {
+        let file_bytes = std::fs::read("../../examples/sample_invoice.pdf").unwrap();
+        let result = run(&file_bytes, Config {
+            scope: Scope::Region,
+            country: Country::Portugal,
+            ..Default::default()
+        });
+        assert_eq!(result.map(|r| r.claim().disclosed.region), Ok(Some("Porto".to_string())));
+    }
+
+    #[test]
+    fn spanish_invoice() {
+        let file_bytes = std::fs::read("../../examples/sample_invoice_es.pdf").unwrap();
+        let result = run(&file_bytes, Config {
+            scope: Scope::Region,
+            country: Country::Spain,
+            ..Default::default()
+        });
+        assert_eq!(result, Ok(Residency {
+            document_id: "B12345674 F2024-0001 15-11-2024".to_string(),
+            issuer_tax_id: "B12345674".to_string(),
+            issue_date: Date::new(2024, 11, 15).unwrap(),
+            first_issue_date: Date::new(2024, 11, 15).unwrap(),
+            expiry: Date::new(2025, 11, 30).unwrap(),
+            validity_days: DEFAULT_VALIDITY_DAYS,
+            postal_code: "28013".to_string(),
+            normalized_address: None,
+            country: Country::Spain,
+            scope: Scope::Region,
+            location: "Madrid".to_string(),
+            disclosed: Disclosed {
+                country: Some(Country::Spain),
+                region: Some("Madrid".to_string()),
+                issue_month: Some("2024-11".to_string()),
+                expiry: Some(Date::new(2025, 11, 30).unwrap()),
+                ..Default::default()
+            },
+            disclosure: Disclosure::for_scope(Scope::Region).commitment(),
+            qr_fields: Default::default(),
+            trusted_issuers: None,
+            trusted_roots: None,
+            dkim_key: None,
+            dkim_domain: None,
+            dkim_selector: None,
+            freshness: None,
+            holder: None,
+            address: None,
+            resident_since_at_least: None,
+            nullifier_domain: "proof-of-residency".to_string(),
+            nullifiers: vec![nullifier("proof-of-residency", "B12345674 F2024-0001 15-11-2024", "B12345674")],
+        }));
+    }
+
+    #[test]
+    fn rejects_untrusted_issuer() {
+        let file_bytes = std::fs::read("../../examples/sample_invoice.pdf").unwrap();
+        let result = run(&file_bytes, Config {
+            scope: Scope::Country,
+            country: Country::Portugal,
+            trusted_issuers: Some(TrustedIssuers::known(Country::Portugal)),
+            ..Default::default()
         });
-        assert_eq!(result, true);
+        assert_eq!(result, Err(ResidencyError::UnknownIssuer));
+    }
+
+    #[test]
+    fn commits_trusted_issuers_policy() {
+        let file_bytes = std::fs::read("../../examples/sample_invoice.pdf").unwrap();
+        let trusted_issuers = TrustedIssuers::new(["501234560", "503504564"]);
+        let claim = run(&file_bytes, Config {
+            scope: Scope::Country,
+            country: Country::Portugal,
+            trusted_issuers: Some(trusted_issuers.clone()),
+            ..Default::default()
+        })
+        .unwrap()
+        .claim();
+        assert_eq!(claim.trusted_issuers, Some(trusted_issuers.commitment()));
+    }
+
+    #[test]
+    fn requires_signed_bills_in_strict_mode() {
+        let trusted_roots =
+            TrustedRoots::new([std::fs::read("../../examples/test_root_ca.der").unwrap()]);
+        let config = || Config {
+            trusted_roots: Some(trusted_roots.clone()),
+            ..Default::default()
+        };
+
+        let signed = std::fs::read("../../examples/sample_invoice_signed.pdf").unwrap();
+        let claim = run(&signed, config()).unwrap().claim();
+        assert_eq!(claim.trusted_roots, Some(trusted_roots.commitment()));
+        assert_eq!(run(&signed, Config::default()).unwrap().claim().trusted_roots, None);
+
+        let unsigned = std::fs::read("../../examples/sample_invoice.pdf").unwrap();
+        assert_eq!(run(&unsigned, config()), Err(ResidencyError::NoSignature));
+
+        // a certificate under the same root, but of someone else than the issuer
+        let other = std::fs::read("../../examples/sample_invoice_signed_other.pdf").unwrap();
+        assert_eq!(run(&other, config()), Err(ResidencyError::UntrustedSigner));
+    }
+
+    #[test]
+    fn rejects_stale_bills() {
+        let file_bytes = std::fs::read("../../examples/sample_invoice.pdf").unwrap();
+        let config = |today: &str| Config {
+            scope: Scope::Country,
+            country: Country::Portugal,
+            freshness: Some(Freshness {
+                today: today.parse().unwrap(),
+                max_age_days: 90,
+            }),
+            ..Default::default()
+        };
+
+        let claim = run(&file_bytes, config("2025-03-07")).unwrap().claim();
+        assert_eq!(claim.disclosed.issue_month, Some("2024-12".to_string()));
+        assert_eq!(run(&file_bytes, config("2025-03-08")), Err(ResidencyError::StaleDocument));
+        assert_eq!(run(&file_bytes, config("2024-12-06")), Err(ResidencyError::FutureIssueDate));
+    }
+
+    #[test]
+    fn binds_claim_to_holder() {
+        let file_bytes = std::fs::read("../../examples/sample_invoice.pdf").unwrap();
+        let config = |id: HolderId| Config {
+            scope: Scope::Country,
+            country: Country::Portugal,
+            holder: Some(Holder { id, salt: [1; 32] }),
+            ..Default::default()
+        };
+
+        let name = HolderId::Name("Maria Santos".to_string());
+        let claim = run(&file_bytes, config(name.clone())).unwrap().claim();
+        assert_eq!(claim.holder, Some(Holder { id: name, salt: [1; 32] }.commitment()));
+        assert!(run(&file_bytes, config(HolderId::TaxId("123456789".to_string()))).is_ok());
+        assert_eq!(
+            run(&file_bytes, config(HolderId::TaxId("501234560".to_string()))),
+            Err(ResidencyError::HolderMismatch)
+        );
+        assert_eq!(
+            run(&file_bytes, config(HolderId::Name("João Silva".to_string()))),
+            Err(ResidencyError::HolderMismatch)
+        );
+    }
+
+    #[test]
+    fn expiry_follows_validity_window() {
+        let file_bytes = std::fs::read("../../examples/sample_invoice.pdf").unwrap();
+        let claim = run(&file_bytes, Config {
+            validity_days: 30,
+            ..Default::default()
+        })
+        .unwrap()
+        .claim();
+        // 30 days after 2024-12-31, to the end of the month
+        assert_eq!(claim.disclosed.expiry, Date::new(2025, 1, 31));
+        assert_eq!(claim.validity_days, 30);
+
+        let config = Config {
+            validity_days: 36_500,
+            ..Default::default()
+        };
+        assert_eq!(run(&file_bytes, config), Err(ResidencyError::ValidityTooLong));
+    }
+
+    #[test]
+    fn nullifier_depends_on_bill_and_application() {
+        let file_bytes = std::fs::read("../../examples/sample_invoice.pdf").unwrap();
+        let claim = |scope: Scope, nullifier_domain: &str| {
+            run(&file_bytes, Config {
+                scope,
+                nullifier_domain: nullifier_domain.to_string(),
+                ..Default::default()
+            })
+            .unwrap()
+            .claim()
+        };
+
+        assert_eq!(claim(Scope::Country, "app").nullifiers, claim(Scope::City, "app").nullifiers);
+        assert_ne!(claim(Scope::Country, "app").nullifiers, claim(Scope::Country, "other").nullifiers);
+    }
+
+    #[test]
+    fn reveals_exactly_the_requested_attributes() {
+        let file_bytes = std::fs::read("../../examples/sample_invoice.pdf").unwrap();
+        let claim = |disclosure: &str| {
+            run(&file_bytes, Config {
+                disclosure: Some(disclosure.parse().unwrap()),
+                ..Default::default()
+            })
+            .map(|residency| residency.claim())
+        };
+
+        let disclosure: Disclosure = "region,municipality,postal_prefix".parse().unwrap();
+        let disclosed = claim("region,municipality,postal_prefix").unwrap();
+        assert_eq!(disclosed.disclosed, Disclosed {
+            region: Some("Porto".to_string()),
+            municipality: Some("Porto".to_string()),
+            postal_prefix: Some("4050".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(disclosed.disclosure, disclosure.commitment());
+
+        // nothing is revealed unless asked for, not even the country, nor the expiry that would
+        // give away the issue month
+        assert_eq!(claim("").unwrap().disclosed, Disclosed::default());
+        let json = serde_json::to_string(&claim("").unwrap()).unwrap();
+        assert!(!json.contains("2024-12") && !json.contains("2025-12"));
+        assert_eq!(claim("expiry").unwrap().disclosed.expiry, Date::new(2025, 12, 31));
+        // the issuer of the sample invoice is not a known utility
+        assert_eq!(claim("issuer_category"), Err(ResidencyError::UnknownIssuerCategory));
+    }
+
+    #[test]
+    fn commits_salted_address() {
+        let first = std::fs::read("../../examples/sample_invoice.pdf").unwrap();
+        let second = std::fs::read("../../examples/sample_invoice_2.pdf").unwrap();
+        let claim = |pdf_bytes: &[u8], salt| {
+            run(pdf_bytes, Config {
+                address_salt: Some(salt),
+                ..Default::default()
+            })
+            .unwrap()
+            .claim()
+        };
+
+        let address = Address {
+            street: "rua de exemplo".to_string(),
+            number: Some("123".to_string()),
+            floor: None,
+            postal_code: "4050-123".to_string(),
+        };
+        assert_eq!(claim(&first, [3; 32]).address, Some(address.commitment(&[3; 32])));
+        // two bills for the same address can be linked by whoever chose the salt only
+        assert_eq!(claim(&first, [3; 32]).address, claim(&second, [3; 32]).address);
+        assert_ne!(claim(&first, [3; 32]).address, claim(&first, [4; 32]).address);
+        assert_eq!(run(&first, Config::default()).unwrap().claim().address, None);
+    }
+
+    #[test]
+    fn combines_bills_from_distinct_issuers() {
+        let first = std::fs::read("../../examples/sample_invoice.pdf").unwrap();
+        let second = std::fs::read("../../examples/sample_invoice_2.pdf").unwrap();
+        let config = || Config {
+            scope: Scope::City,
+            freshness: Some(Freshness {
+                today: Date::new(2025, 2, 1).unwrap(),
+                max_age_days: 90,
+            }),
+            ..Default::default()
+        };
+
+        let claim = run_all(&[&first, &second], config()).unwrap().claim();
+        assert_eq!(claim.disclosed.municipality, Some("Porto".to_string()));
+        assert_eq!(claim.disclosed.issue_month, Some("2024-12".to_string()));
+        // the claim lasts as long as the oldest bill
+        assert_eq!(claim.disclosed.expiry, Date::new(2025, 11, 30));
+        assert_eq!(claim.nullifiers, vec![
+            nullifier("proof-of-residency", "ABC12345-0001", "501234560"),
+            nullifier("proof-of-residency", "XYZ98765-0042", "507654323"),
+        ]);
+
+        assert_eq!(run_all(&[&first, &first], config()), Err(ResidencyError::DuplicateIssuer));
+        assert_eq!(run_all::<Vec<u8>>(&[], config()), Err(ResidencyError::NoDocument));
+        // every bill must pass the checks on its own
+        let late = Config {
+            freshness: Some(Freshness {
+                today: Date::new(2025, 3, 1).unwrap(),
+                max_age_days: 90,
+            }),
+            ..config()
+        };
+        assert_eq!(run_all(&[&first, &second], late), Err(ResidencyError::StaleDocument));
+
+        let residency = run(&first, config()).unwrap();
+        let elsewhere = Residency {
+            issuer_tax_id: "507654323".to_string(),
+            postal_code: "1000-001".to_string(),
+            ..residency.clone()
+        };
+        assert_eq!(
+            Residency::combine(vec![residency.clone(), elsewhere]),
+            Err(ResidencyError::AddressMismatch)
+        );
+        let same_month = Residency {
+            issuer_tax_id: "507654323".to_string(),
+            issue_date: Date::new(2024, 12, 20).unwrap(),
+            ..residency.clone()
+        };
+        assert_eq!(
+            Residency::combine(vec![residency, same_month]),
+            Err(ResidencyError::DuplicateMonth)
+        );
+    }
+
+    #[test]
+    fn proves_residency_period_without_dates() {
+        let newest = std::fs::read("../../examples/sample_invoice.pdf").unwrap();
+        let oldest = std::fs::read("../../examples/sample_invoice_3.pdf").unwrap();
+        let config = |months| Config {
+            min_residency_months: Some(months),
+            ..Default::default()
+        };
+
+        // from 2024-05-20 to 2024-12-07
+        let claim = run_all(&[&oldest, &newest], config(6)).unwrap().claim();
+        assert_eq!(claim.resident_since_at_least, Some(6));
+        assert_eq!(claim.disclosed.expiry, Some(run(&newest, Config::default()).unwrap().expiry));
+        let json = serde_json::to_string(&claim).unwrap();
+        assert!(!json.contains("2024-05") && !json.contains("2025-05"));
+
+        assert_eq!(run_all(&[&oldest, &newest], config(7)), Err(ResidencyError::ResidencyTooShort));
+        assert_eq!(run(&newest, config(1)), Err(ResidencyError::ResidencyTooShort));
+        assert_eq!(run(&newest, Config::default()).unwrap().claim().resident_since_at_least, None);
+    }
+
+    #[test]
+    fn unregistered_country_is_unsupported() {
+        let file_bytes = std::fs::read("../../examples/sample_invoice.pdf").unwrap();
+        let result = run_with(&Registry::new(), &file_bytes, Config {
+            scope: Scope::Country,
+            country: Country::Portugal,
+            ..Default::default()
+        });
+        assert_eq!(result, Err(ResidencyError::UnsupportedCountry(Country::Portugal)));
+    }
+
+    #[test]
+    fn rejects_non_pdf_input() {
+        let result = run(b"not a pdf", Config {
+            scope: Scope::Country,
+            country: Country::Portugal,
+            ..Default::default()
+        });
+        assert!(matches!(result, Err(ResidencyError::PdfParse(_))));
+
+        let file_bytes = std::fs::read("../../examples/sample_invoice.pdf").unwrap();
+        let truncated = run(&file_bytes[..file_bytes.len() / 2], Config::default());
+        assert!(matches!(truncated, Err(ResidencyError::PdfParse(_))));
+    }
+
+    #[test]
+    fn accepts_bills_delivered_by_email() {
+        let file_bytes = std::fs::read("../../examples/sample_invoice.pdf").unwrap();
+        let expected = run(&file_bytes, Config::default()).unwrap().nullifiers;
+
+        let email = std::fs::read("../../examples/sample_invoice.eml").unwrap();
+        assert_eq!(run(&email, Config::default()).map(|r| r.nullifiers), Ok(expected.clone()));
+
+        use base64::Engine;
+        let encoded = base64::engine::general_purpose::STANDARD.encode(&file_bytes);
+        let result = run(encoded.as_bytes(), Config::default());
+        assert_eq!(result.map(|r| r.nullifiers), Ok(expected));
+    }
+
+    #[test]
+    fn commits_dkim_signer_of_emailed_bills() {
+        let key: DkimKey = std::fs::read_to_string("../../examples/dkim_test_key.txt")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let config = || Config {
+            dkim_key: Some(key.clone()),
+            ..Default::default()
+        };
+
+        let email = std::fs::read("../../examples/sample_invoice_dkim.eml").unwrap();
+        let claim = run(&email, config()).unwrap().claim();
+        assert_eq!(claim.dkim_key, Some(key.commitment()));
+        assert_eq!(claim.dkim_domain.as_deref(), Some("exemplo-energia.pt"));
+        assert_eq!(claim.dkim_selector.as_deref(), Some("fatura2024"));
+        assert_eq!(run(&email, Config::default()).unwrap().claim().dkim_domain, None);
+
+        // the PDF alone, or an unsigned email, says nothing about who sent it
+        let pdf = std::fs::read("../../examples/sample_invoice.pdf").unwrap();
+        assert_eq!(run(&pdf, config()), Err(ResidencyError::NoDkimSignature));
+        let unsigned = std::fs::read("../../examples/sample_invoice.eml").unwrap();
+        assert_eq!(run(&unsigned, config()), Err(ResidencyError::NoDkimSignature));
+    }
+
+    #[test]
+    fn reads_bills_written_by_other_software() {
+        let file_bytes = std::fs::read("../../examples/sample_invoice.pdf").unwrap();
+        let expected = run(&file_bytes, Config::default()).unwrap().nullifiers;
+
+        for file in [
+            // compressed object and cross-reference streams
+            "sample_invoice_objstm.pdf",
+            // a Type0 font whose text is only readable through its ToUnicode CMap
+            "sample_invoice_cid.pdf",
+            // encrypted with an empty user password
+            "sample_invoice_encrypted.pdf",
+            // a ToUnicode CMap pdf-extract cannot parse
+            "sample_invoice_broken_font.pdf",
+        ] {
+            let file_bytes = std::fs::read(format!("../../examples/{}", file)).unwrap();
+            let result = run(&file_bytes, Config::default());
+            assert_eq!(result.map(|r| r.nullifiers), Ok(expected.clone()), "{}", file);
+        }
     }
 }
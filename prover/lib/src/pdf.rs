@@ -0,0 +1,369 @@
+// prover/lib/src/pdf.rs
+//! Access to the text of a PDF, and to the images embedded in it, which is where invoicing
+//! software draws QR codes.
+mod content;
+
+use crate::qr::GrayImage;
+use crate::ResidencyError;
+use flate2::read::ZlibDecoder;
+use lopdf::{Dictionary, Document, Object};
+use std::io::Read;
+
+/// The most pixels of an image that is decoded, far more than a QR code needs. Larger images are
+/// skipped rather than allocated, the sizes come from the document.
+const MAX_PIXELS: usize = 1 << 22;
+
+/// The most bytes a stream is decompressed to, enough for an image of [`MAX_PIXELS`] in CMYK.
+const MAX_DECODED_BYTES: usize = 4 * MAX_PIXELS;
+
+/// Parses the document, including compressed object and cross-reference streams.
+///
+/// Encrypted documents are decrypted with the empty user password: bills are often encrypted only
+/// to restrict printing or copying, and open without one.
+pub(crate) fn load(pdf_bytes: &[u8]) -> Result<Document, ResidencyError> {
+    let mut document =
+        Document::load_mem(pdf_bytes).map_err(|e| ResidencyError::PdfParse(e.to_string()))?;
+    if document.is_encrypted() {
+        document
+            .decrypt("")
+            .map_err(|_| ResidencyError::EncryptedDocument)?;
+    }
+    Ok(document)
+}
+
+/// Returns the text of the document, from the first extractor that finds any.
+///
+/// The text is first laid out where the content stream of each page draws it, see [`content`],
+/// so that the columns of a bill stay apart and at their position. Its strings are decoded by
+/// lopdf, from the encoding of simple fonts and the ToUnicode CMap of CID fonts. If a font or a
+/// content stream cannot be read, lopdf's own extraction is tried, one line per text object,
+/// which reports what it cannot read as an error. pdf-extract lays the text out by position too
+/// but panics on some fonts it cannot parse, it is only tried when lopdf finds no text. A panic
+/// can only be caught natively, the program is built with panics aborting, so pdf-extract is left
+/// out of the zkVM: there a bill lopdf finds no text in is rejected with
+/// [`ResidencyError::NoText`], even if it reads natively.
+pub(crate) fn text(document: &Document) -> Result<String, ResidencyError> {
+    let extractors: [fn(&Document) -> Option<String>; 3] =
+        [positioned_text, plain_text, layout_text];
+    extractors
+        .iter()
+        .filter_map(|extract| extract(document))
+        .find(|text| text.chars().any(char::is_alphanumeric))
+        .ok_or(ResidencyError::NoText)
+}
+
+#[cfg(not(target_os = "zkvm"))]
+fn layout_text(document: &Document) -> Option<String> {
+    use std::panic::{self, AssertUnwindSafe};
+
+    let mut text = String::new();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut output = pdf_extract::PlainTextOutput::new(&mut text);
+        pdf_extract::output_doc(document, &mut output)
+    }));
+    matches!(result, Ok(Ok(()))).then_some(text)
+}
+
+#[cfg(target_os = "zkvm")]
+fn layout_text(_document: &Document) -> Option<String> {
+    None
+}
+
+fn positioned_text(document: &Document) -> Option<String> {
+    let pages: Option<Vec<String>> = document
+        .get_pages()
+        .into_values()
+        .map(|page_id| content::page_text(document, page_id))
+        .collect();
+    Some(pages?.join("\n\n"))
+}
+
+fn plain_text(document: &Document) -> Option<String> {
+    let pages: Vec<u32> = document.get_pages().into_keys().collect();
+    document.extract_text(&pages).ok()
+}
+
+/// Returns every image of the document that can be decoded, converted to grayscale.
+///
+/// Images in a format this module does not read (e.g. JPEG photos) are skipped. A stream that
+/// decompresses to more than the images this module decodes, or whose parameters do not fit in
+/// memory, fails the document with [`ResidencyError::PdfParse`].
+pub(crate) fn images(document: &Document) -> Result<Vec<GrayImage>, ResidencyError> {
+    let mut images = Vec::new();
+    for stream in document
+        .objects
+        .values()
+        .filter_map(|object| object.as_stream().ok())
+        .filter(|stream| {
+            stream
+                .dict
+                .get(b"Subtype")
+                .and_then(Object::as_name)
+                .is_ok_and(|subtype| subtype == b"Image")
+        })
+    {
+        if let Some(data) = decompress(&stream.dict, &stream.content)? {
+            images.extend(to_gray(document, &stream.dict, &data));
+        }
+    }
+    Ok(images)
+}
+
+// The decoded content of a stream, `None` if it is in a format this module does not read
+fn decompress(dict: &Dictionary, content: &[u8]) -> Result<Option<Vec<u8>>, ResidencyError> {
+    let too_large = || ResidencyError::PdfParse("stream too large to decode".to_string());
+    let filters: Option<Vec<&[u8]>> = match dict.get(b"Filter") {
+        Err(_) => Some(vec![]),
+        Ok(Object::Array(filters)) => filters.iter().map(Object::as_name).collect::<Result<_, _>>().ok(),
+        Ok(filter) => filter.as_name().ok().map(|filter| vec![filter]),
+    };
+
+    match filters.as_deref() {
+        Some([]) => Ok(Some(content.to_vec())),
+        Some([b"FlateDecode" | b"Fl"]) => {
+            let mut data = Vec::new();
+            let limit = MAX_DECODED_BYTES as u64 + 1;
+            if ZlibDecoder::new(content).take(limit).read_to_end(&mut data).is_err() {
+                return Ok(None);
+            }
+            if data.len() > MAX_DECODED_BYTES {
+                return Err(too_large());
+            }
+
+            let params = match dict.get(b"DecodeParms") {
+                Ok(Object::Dictionary(params)) => Some(params),
+                _ => None,
+            };
+            let predictor = params.and_then(|p| integer(p, b"Predictor")).unwrap_or(1);
+            if predictor < 10 {
+                return Ok((predictor == 1).then_some(data));
+            }
+
+            let Some(params) = params else {
+                return Ok(None);
+            };
+            let colors = integer(params, b"Colors").unwrap_or(1);
+            let bits = integer(params, b"BitsPerComponent").unwrap_or(8);
+            let columns = integer(params, b"Columns").unwrap_or(1);
+            // the parameters come from the document, a row is never longer than the data
+            let pixel_bits = colors.checked_mul(bits).ok_or_else(too_large)?;
+            let row_len = pixel_bits
+                .checked_mul(columns)
+                .map(|row_bits| row_bits.div_ceil(8))
+                .filter(|&row_len| row_len <= MAX_DECODED_BYTES)
+                .ok_or_else(too_large)?;
+            Ok(unpredict_png(&data, pixel_bits.div_ceil(8).max(1), row_len))
+        }
+        _ => Ok(None),
+    }
+}
+
+// Reverses the PNG filter applied to every row, `bpp` is the number of bytes per pixel.
+fn unpredict_png(data: &[u8], bpp: usize, row_len: usize) -> Option<Vec<u8>> {
+    if row_len == 0 || row_len >= data.len() {
+        return None;
+    }
+    let mut output: Vec<u8> = Vec::with_capacity(data.len());
+    let mut previous = vec![0u8; row_len];
+
+    for row in data.chunks(row_len + 1) {
+        let (&filter, row) = row.split_first()?;
+        if row.len() != row_len {
+            return None;
+        }
+
+        let mut current = row.to_vec();
+        for i in 0..row_len {
+            let left = if i >= bpp { current[i - bpp] } else { 0 };
+            let up = previous[i];
+            let up_left = if i >= bpp { previous[i - bpp] } else { 0 };
+            let prediction = match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((u16::from(left) + u16::from(up)) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => return None,
+            };
+            current[i] = current[i].wrapping_add(prediction);
+        }
+        output.extend_from_slice(&current);
+        previous = current;
+    }
+
+    Some(output)
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = i16::from(a) + i16::from(b) - i16::from(c);
+    let (pa, pb, pc) = ((p - i16::from(a)).abs(), (p - i16::from(b)).abs(), (p - i16::from(c)).abs());
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// How the samples of an image map to gray levels.
+enum ColorSpace {
+    /// `n` components, averaged (or subtracted from white for CMYK).
+    Device { components: usize, subtractive: bool },
+    /// A palette of gray levels indexed by the single component.
+    Indexed(Vec<u8>),
+}
+
+fn color_space(document: &Document, object: &Object) -> Option<ColorSpace> {
+    let device = |components, subtractive| Some(ColorSpace::Device { components, subtractive });
+
+    match document.dereference(object).ok()?.1 {
+        Object::Name(name) => match name.as_slice() {
+            b"DeviceGray" | b"G" | b"CalGray" => device(1, false),
+            b"DeviceRGB" | b"RGB" | b"CalRGB" => device(3, false),
+            b"DeviceCMYK" | b"CMYK" => device(4, true),
+            _ => None,
+        },
+        Object::Array(array) => match array.first()?.as_name().ok()? {
+            b"CalGray" => device(1, false),
+            b"CalRGB" => device(3, false),
+            b"ICCBased" => {
+                let profile = document.dereference(array.get(1)?).ok()?.1.as_stream().ok()?;
+                let components = integer(&profile.dict, b"N")?;
+                device(components, components == 4)
+            }
+            b"Indexed" | b"I" => {
+                let ColorSpace::Device { components, subtractive } =
+                    color_space(document, array.get(1)?)?
+                else {
+                    return None;
+                };
+                let palette = match document.dereference(array.get(3)?).ok()?.1 {
+                    Object::String(bytes, _) => bytes.clone(),
+                    Object::Stream(stream) => decompress(&stream.dict, &stream.content).ok()??,
+                    _ => return None,
+                };
+                let levels = palette
+                    .chunks_exact(components)
+                    .map(|color| gray(color, subtractive))
+                    .collect();
+                Some(ColorSpace::Indexed(levels))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn gray(color: &[u8], subtractive: bool) -> u8 {
+    if subtractive {
+        let ink = color.iter().map(|&c| usize::from(c)).sum::<usize>().min(255);
+        (255 - ink) as u8
+    } else {
+        (color.iter().map(|&c| usize::from(c)).sum::<usize>() / color.len()) as u8
+    }
+}
+
+fn to_gray(document: &Document, dict: &Dictionary, data: &[u8]) -> Option<GrayImage> {
+    let width = integer(dict, b"Width")?;
+    let height = integer(dict, b"Height")?;
+    let image_mask = dict.get(b"ImageMask").and_then(Object::as_bool).unwrap_or(false);
+
+    let (bits, color_space) = if image_mask {
+        // stencil masks paint the 0 samples, in black for all we care
+        (1, ColorSpace::Indexed(vec![0, 255]))
+    } else {
+        let bits = integer(dict, b"BitsPerComponent")?;
+        (bits, color_space(document, dict.get(b"ColorSpace").ok()?)?)
+    };
+    if !matches!(bits, 1 | 2 | 4 | 8) {
+        return None;
+    }
+
+    let components = match &color_space {
+        ColorSpace::Device { components, .. } => *components,
+        ColorSpace::Indexed(_) => 1,
+    };
+    let size = width.checked_mul(height).filter(|&size| size <= MAX_PIXELS)?;
+    let row_len = width.checked_mul(components)?.checked_mul(bits)?.div_ceil(8);
+    if data.len() < row_len.checked_mul(height)? {
+        return None;
+    }
+
+    // a Decode array of [1 0] inverts single-component images
+    let inverted = match dict.get(b"Decode") {
+        Ok(Object::Array(decode)) if components == 1 => {
+            decode.first().and_then(|d| d.as_float().ok()).is_some_and(|d| d > 0.5)
+        }
+        _ => false,
+    };
+
+    let max = (1u16 << bits) - 1;
+    let mut pixels = Vec::with_capacity(size);
+    let mut color = vec![0u8; components];
+    for row in data.chunks(row_len).take(height) {
+        for x in 0..width {
+            for (c, value) in color.iter_mut().enumerate() {
+                let bit = (x * components + c) * bits;
+                let sample = u16::from(row[bit / 8] >> (8 - bits - bit % 8)) & max;
+                let sample = if inverted { max - sample } else { sample };
+                *value = match color_space {
+                    ColorSpace::Indexed(_) => sample as u8,
+                    ColorSpace::Device { .. } => (sample * 255 / max) as u8,
+                };
+            }
+            pixels.push(match &color_space {
+                ColorSpace::Device { subtractive, .. } => gray(&color, *subtractive),
+                ColorSpace::Indexed(levels) => *levels.get(usize::from(color[0]))?,
+            });
+        }
+    }
+
+    Some(GrayImage { width, height, pixels })
+}
+
+fn integer(dict: &Dictionary, key: &[u8]) -> Option<usize> {
+    dict.get(key).and_then(Object::as_i64).ok()?.try_into().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_images_too_large_to_decode() {
+        let image = |width: i64, height: i64| {
+            let mut dict = Dictionary::new();
+            dict.set("Width", width);
+            dict.set("Height", height);
+            dict.set("BitsPerComponent", 8);
+            dict.set("ColorSpace", Object::Name(b"DeviceRGB".to_vec()));
+            to_gray(&Document::new(), &dict, &[0; 12])
+        };
+        assert!(image(2, 2).is_some());
+        assert!(image(1 << 62, 1).is_none());
+        assert!(image(1 << 32, 1 << 32).is_none());
+        assert!(image(1 << 12, 1 << 12).is_none());
+    }
+
+    #[test]
+    fn rejects_predictors_too_large_to_decode() {
+        let stream = |columns: i64, colors: i64| {
+            let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            std::io::Write::write_all(&mut encoder, &[0, 1, 2, 3]).unwrap();
+            let mut params = Dictionary::new();
+            params.set("Predictor", 12);
+            params.set("Columns", columns);
+            params.set("Colors", colors);
+            let mut dict = Dictionary::new();
+            dict.set("Filter", Object::Name(b"FlateDecode".to_vec()));
+            dict.set("DecodeParms", params);
+            decompress(&dict, &encoder.finish().unwrap())
+        };
+        assert_eq!(stream(3, 1), Ok(Some(vec![1, 2, 3])));
+        assert!(matches!(stream(1 << 40, 1), Err(ResidencyError::PdfParse(_))));
+        assert!(matches!(stream(1 << 40, 1 << 40), Err(ResidencyError::PdfParse(_))));
+        // a row longer than the data is malformed, not allocated
+        assert_eq!(stream(1 << 20, 1), Ok(None));
+    }
+}
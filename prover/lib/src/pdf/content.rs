@@ -0,0 +1,373 @@
+// prover/lib/src/pdf/content.rs
+//! The text of a page laid out where its content stream draws it. The text positioning operators
+//! (`Tm`, `Td`, `TD`, `T*`, ...) and the transformations of the graphics state are followed to
+//! find where every string is shown, and the page is written line by line, each string at the
+//! column of its position.
+use lopdf::content::{Content, Operation};
+use lopdf::{Dictionary, Document, Encoding, Object, ObjectId};
+use std::collections::BTreeMap;
+
+/// The width of a column of the laid out text, in points: the advance of an average character of
+/// a 9 point font, the size most bills are printed in.
+const COLUMN_WIDTH: f32 = 5.0;
+
+/// Below this gap, in font sizes, a string continues the word before it.
+const WORD_GAP: f32 = 0.1;
+
+/// From this gap on, in font sizes, a string starts a cell of its own rather than the next word
+/// of the one before it.
+const CELL_GAP: f32 = 1.0;
+
+/// The advances of the printable ASCII characters in Helvetica, in thousandths of the font size.
+/// They stand in for the widths of fonts that do not give theirs, as the standard fonts bills
+/// are often printed in do not.
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722,
+    667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944,
+    667, 667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222,
+    500, 222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334,
+    584,
+];
+
+/// The advance of any other character, as of most accented letters.
+const DEFAULT_WIDTH: f32 = 556.0;
+
+/// An affine transformation `[a b c d e f]`, as PDF writes them.
+type Matrix = [f32; 6];
+
+const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// A string shown on a page, from the start of its first glyph to the end of its last, in the
+/// coordinates of the page.
+#[derive(Debug, Clone, PartialEq)]
+struct Run {
+    x: f32,
+    end: f32,
+    y: f32,
+    /// The size of the font on the page.
+    size: f32,
+    text: String,
+}
+
+struct Font<'a> {
+    encoding: Encoding<'a>,
+    /// The code of the first width, and the widths, of simple fonts that give them.
+    widths: Option<(usize, Vec<f32>)>,
+    /// Whether codes take two bytes, as in the CID fonts of `Type0` fonts.
+    two_bytes: bool,
+}
+
+impl<'a> Font<'a> {
+    fn new(document: &'a Document, dict: &'a Dictionary) -> Option<Self> {
+        let encoding = dict.get_font_encoding(document).ok()?;
+        let two_bytes = dict.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Type0");
+        let first_char = dict.get(b"FirstChar").and_then(Object::as_i64).ok();
+        let widths = dict
+            .get(b"Widths")
+            .and_then(|widths| document.dereference(widths))
+            .and_then(|(_, widths)| widths.as_array())
+            .ok()
+            .map(|widths| widths.iter().map(|w| w.as_float().unwrap_or(0.0)).collect());
+
+        Some(Self {
+            encoding,
+            widths: first_char
+                .and_then(|first| usize::try_from(first).ok())
+                .zip(widths)
+                .filter(|_| !two_bytes),
+            two_bytes,
+        })
+    }
+
+    // The advance of the glyphs of `bytes`, decoded to `text`, in thousandths of the font size
+    fn advance(&self, bytes: &[u8], text: &str) -> f32 {
+        let estimate = |c: char| match u32::from(c).checked_sub(32) {
+            Some(i) if i < 95 => f32::from(HELVETICA_WIDTHS[i as usize]),
+            _ => DEFAULT_WIDTH,
+        };
+        match &self.widths {
+            Some((first, widths)) => bytes
+                .iter()
+                .map(|&code| {
+                    let width = usize::from(code).checked_sub(*first).and_then(|i| widths.get(i));
+                    width.copied().unwrap_or_else(|| estimate(char::from(code)))
+                })
+                .sum(),
+            None => text.chars().map(estimate).sum(),
+        }
+    }
+}
+
+/// The state the position of the strings shown depends on.
+struct State<'a> {
+    ctm: Matrix,
+    saved: Vec<Matrix>,
+    text_matrix: Matrix,
+    line_matrix: Matrix,
+    font: Option<&'a Font<'a>>,
+    font_size: f32,
+    char_spacing: f32,
+    word_spacing: f32,
+    /// The horizontal scaling, as a fraction.
+    scale: f32,
+    leading: f32,
+}
+
+impl State<'_> {
+    fn next_line(&mut self, tx: f32, ty: f32) {
+        self.line_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, tx, ty], &self.line_matrix);
+        self.text_matrix = self.line_matrix;
+    }
+
+    fn advance(&mut self, tx: f32) {
+        self.text_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, tx, 0.0], &self.text_matrix);
+    }
+
+    // The run of the string `bytes`, which moves the text position to its end. Strings in fonts
+    // the page does not have cannot be decoded.
+    fn show(&mut self, bytes: &[u8]) -> Option<Run> {
+        let font = self.font?;
+        let text = Document::decode_text(&font.encoding, bytes).ok()?;
+        let codes = if font.two_bytes { bytes.len() / 2 } else { bytes.len() };
+        // word spacing only applies to the single byte code 32
+        let spaces = if font.two_bytes { 0 } else { bytes.iter().filter(|&&b| b == b' ').count() };
+        let tx = (font.advance(bytes, &text) / 1000.0 * self.font_size
+            + self.char_spacing * codes as f32
+            + self.word_spacing * spaces as f32)
+            * self.scale;
+
+        let [a, b, c, d, x, y] = multiply(&self.text_matrix, &self.ctm);
+        self.advance(tx);
+        Some(Run {
+            x,
+            end: x + tx * a.hypot(b),
+            y,
+            size: self.font_size * c.hypot(d),
+            text,
+        })
+    }
+}
+
+/// The text of the page, one line per baseline, or `None` if its content or one of its fonts
+/// cannot be read.
+pub(super) fn page_text(document: &Document, page_id: ObjectId) -> Option<String> {
+    let mut fonts = BTreeMap::new();
+    for (name, dict) in document.get_page_fonts(page_id).ok()? {
+        fonts.insert(name, Font::new(document, dict)?);
+    }
+    let content = Content::decode(&document.get_page_content(page_id).ok()?).ok()?;
+
+    Some(lay_out(runs(&content.operations, &fonts)))
+}
+
+fn runs(operations: &[Operation], fonts: &BTreeMap<Vec<u8>, Font>) -> Vec<Run> {
+    let mut state = State {
+        ctm: IDENTITY,
+        saved: Vec::new(),
+        text_matrix: IDENTITY,
+        line_matrix: IDENTITY,
+        font: None,
+        font_size: 0.0,
+        char_spacing: 0.0,
+        word_spacing: 0.0,
+        scale: 1.0,
+        leading: 0.0,
+    };
+    let mut runs = Vec::new();
+
+    for operation in operations {
+        let operands = &operation.operands;
+        let number = |i: usize| operands.get(i).and_then(|o| o.as_float().ok()).unwrap_or(0.0);
+        let matrix = || [0, 1, 2, 3, 4, 5].map(number);
+        match operation.operator.as_str() {
+            "q" => state.saved.push(state.ctm),
+            "Q" => state.ctm = state.saved.pop().unwrap_or(state.ctm),
+            "cm" => state.ctm = multiply(&matrix(), &state.ctm),
+            "BT" => {
+                state.text_matrix = IDENTITY;
+                state.line_matrix = IDENTITY;
+            }
+            "Tf" => {
+                state.font = operands
+                    .first()
+                    .and_then(|name| name.as_name().ok())
+                    .and_then(|name| fonts.get(name));
+                state.font_size = number(1);
+            }
+            "Tc" => state.char_spacing = number(0),
+            "Tw" => state.word_spacing = number(0),
+            "Tz" => state.scale = number(0) / 100.0,
+            "TL" => state.leading = number(0),
+            "Td" => state.next_line(number(0), number(1)),
+            "TD" => {
+                state.leading = -number(1);
+                state.next_line(number(0), number(1));
+            }
+            "Tm" => {
+                state.line_matrix = matrix();
+                state.text_matrix = state.line_matrix;
+            }
+            "T*" => state.next_line(0.0, -state.leading),
+            "Tj" | "'" | "\"" => {
+                if operation.operator == "\"" {
+                    state.word_spacing = number(0);
+                    state.char_spacing = number(1);
+                }
+                if operation.operator != "Tj" {
+                    state.next_line(0.0, -state.leading);
+                }
+                let string = operands.last().and_then(|o| o.as_str().ok());
+                runs.extend(string.and_then(|bytes| state.show(bytes)));
+            }
+            "TJ" => {
+                let elements = operands.first().and_then(|o| o.as_array().ok());
+                for element in elements.into_iter().flatten() {
+                    match element {
+                        Object::String(bytes, _) => runs.extend(state.show(bytes)),
+                        // a number moves the next glyph back, in thousandths of the font size
+                        _ => {
+                            let adjustment = element.as_float().unwrap_or(0.0);
+                            state.advance(-adjustment / 1000.0 * state.font_size * state.scale);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    runs
+}
+
+// The lines of the runs, from the top of the page down, each run at the column of its position
+// counted from the leftmost run of the page
+fn lay_out(mut runs: Vec<Run>) -> String {
+    runs.retain(|run| !run.text.is_empty() && run.x.is_finite() && run.y.is_finite());
+    runs.sort_by(|a, b| b.y.total_cmp(&a.y).then(a.x.total_cmp(&b.x)));
+    let left = runs.iter().map(|run| run.x).fold(f32::INFINITY, f32::min);
+
+    // runs within half a font size of the baseline of the first run of a line are on that line
+    let mut lines: Vec<Vec<Run>> = Vec::new();
+    for run in runs {
+        match lines.last_mut() {
+            Some(line) if (line[0].y - run.y).abs() < line[0].size.max(run.size) / 2.0 => {
+                line.push(run)
+            }
+            _ => lines.push(vec![run]),
+        }
+    }
+
+    let lines: Vec<String> = lines
+        .into_iter()
+        .map(|mut line| {
+            line.sort_by(|a, b| a.x.total_cmp(&b.x));
+            lay_out_line(&line, left)
+        })
+        .collect();
+    lines.join("\n")
+}
+
+fn lay_out_line(line: &[Run], left: f32) -> String {
+    let mut text = String::new();
+    let mut width = 0;
+    let mut previous: Option<&Run> = None;
+    for run in line {
+        let gap = previous.map(|previous| (run.x - previous.end) / run.size.max(1.0));
+        match gap {
+            Some(gap) if gap < WORD_GAP => {}
+            Some(gap) if gap < CELL_GAP => {
+                if !text.ends_with(' ') && !run.text.starts_with(' ') {
+                    text.push(' ');
+                    width += 1;
+                }
+            }
+            _ => {
+                // set apart from the previous cell by two spaces at least, so that it stays a
+                // cell of its own however narrow the columns are
+                let column = ((run.x - left) / COLUMN_WIDTH).round().max(0.0) as usize;
+                let column = if previous.is_some() { column.max(width + 2) } else { column };
+                text.push_str(&" ".repeat(column - width));
+                width = column;
+            }
+        }
+        text.push_str(&run.text);
+        width += run.text.chars().count();
+        previous = Some(run);
+    }
+    text
+}
+
+fn multiply(m: &Matrix, n: &Matrix) -> Matrix {
+    [
+        m[0] * n[0] + m[1] * n[2],
+        m[0] * n[1] + m[1] * n[3],
+        m[2] * n[0] + m[3] * n[2],
+        m[2] * n[1] + m[3] * n[3],
+        m[4] * n[0] + m[5] * n[2] + n[4],
+        m[4] * n[1] + m[5] * n[3] + n[5],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(x: f32, y: f32, text: &str) -> Run {
+        // 10 point Helvetica, about 5 points per character
+        let end = x + 5.0 * text.chars().count() as f32;
+        Run { x, end, y, size: 10.0, text: text.to_string() }
+    }
+
+    #[test]
+    fn follows_text_positioning_operators() {
+        let document = Document::new();
+        let mut dict = Dictionary::new();
+        dict.set("Type", Object::Name(b"Font".to_vec()));
+        dict.set("Subtype", Object::Name(b"Type1".to_vec()));
+        dict.set("BaseFont", Object::Name(b"Helvetica".to_vec()));
+        dict.set("Encoding", Object::Name(b"WinAnsiEncoding".to_vec()));
+        let fonts = BTreeMap::from([(b"F1".to_vec(), Font::new(&document, &dict).unwrap())]);
+        let content = Content::decode(
+            b"q 1 0 0 1 50 0 cm BT /F1 10 Tf 14 TL 0 800 Td (Local de consumo:) Tj T* (Rua do Ouro) Tj \
+              1 0 0 1 300 800 Tm [(NIF:) -250 (501234560)] TJ ET Q BT /F1 10 Tf 50 772 Td (Fim) Tj ET",
+        )
+        .unwrap();
+
+        let runs = runs(&content.operations, &fonts);
+        let positions: Vec<(f32, f32, &str)> = runs
+            .iter()
+            .map(|run| ((run.x * 100.0).round() / 100.0, run.y, run.text.as_str()))
+            .collect();
+        assert_eq!(
+            positions,
+            vec![
+                (50.0, 800.0, "Local de consumo:"),
+                (50.0, 786.0, "Rua do Ouro"),
+                (350.0, 800.0, "NIF:"),
+                // after the advance of `NIF:`, and a quarter of the font size more
+                (371.39, 800.0, "501234560"),
+                (50.0, 772.0, "Fim"),
+            ]
+        );
+    }
+
+    #[test]
+    fn lays_out_cells_at_their_position() {
+        let runs = vec![
+            run(300.0, 800.0, "NIF:"),
+            run(50.0, 800.0, "Local de consumo:"),
+            run(50.0, 786.0, "Rua do Ouro,"),
+            run(112.0, 786.0, "12"),
+            run(300.0, 786.0, "5012"),
+            run(320.0, 786.3, "34560"),
+            run(50.0, 772.0, "1100-060 Lisboa"),
+        ];
+        // the second column starts 250 points, 50 columns, to the right of the first
+        let expected = [
+            format!("Local de consumo:{}NIF:", " ".repeat(33)),
+            format!("Rua do Ouro, 12{}501234560", " ".repeat(35)),
+            "1100-060 Lisboa".to_string(),
+        ];
+        assert_eq!(lay_out(runs), expected.join("\n"));
+    }
+}
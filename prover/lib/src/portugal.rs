@@ -1,15 +1,245 @@
 // prover/lib/src/portugal.rs
+use crate::{
+    Address, CountryValidator, Date, ExtractionHints, HolderId, IssuerCategory, KnownIssuer,
+    ResidencyError, Scope,
+};
 use regex::Regex;
+use std::collections::BTreeMap;
 
-pub(crate) fn validate(pdf_text: String) -> bool {
-     // check the text contains a valid Portuguese postal code
-     let postal_code = extract_postal_code(&pdf_text);
+mod address;
+mod layout;
+mod municipalities;
 
-     // check the text contains a valid ATCUD and a valid postal
-     is_valid_atcud(&pdf_text).is_some() && postal_code.is_some()
+pub use address::extract_address;
+pub use layout::{Template, TEMPLATES};
+pub use municipalities::{municipality, region, Municipality};
+
+/// Validates bills carrying an ATCUD, the code that identifies invoices registered with the
+/// Portuguese tax authority.
+pub struct Portugal;
+
+impl CountryValidator for Portugal {
+    fn extraction_hints(&self) -> ExtractionHints {
+        ExtractionHints {
+            markers: &["ATCUD"],
+        }
+    }
+
+    fn document_id(&self, text: &str) -> Option<String> {
+        is_valid_atcud(text)
+    }
+
+    fn issuer_tax_id(&self, text: &str, qr_fields: &BTreeMap<String, String>) -> Option<String> {
+        // the fiscal QR code names the issuer unambiguously, the text may list several NIFs
+        match qr_fields.get("A") {
+            Some(nif) => is_valid_nif(nif).then(|| nif.clone()),
+            None => extract_nifs(text).into_iter().next(),
+        }
+    }
+
+    fn issue_date(&self, text: &str, qr_fields: &BTreeMap<String, String>) -> Option<Date> {
+        match qr_fields.get("F") {
+            Some(date) => parse_compact_date(date),
+            None => extract_issue_date(text),
+        }
+    }
+
+    fn is_customer(&self, text: &str, qr_fields: &BTreeMap<String, String>, holder: &HolderId) -> bool {
+        // the QR code names the customer by NIF, a bill issued to an anonymous consumer is no
+        // one's in particular
+        match (holder, qr_fields.get("B")) {
+            (HolderId::TaxId(nif), Some(customer)) => {
+                customer != ANONYMOUS_CONSUMER && is_valid_nif(nif) && nif == customer
+            }
+            (HolderId::TaxId(nif), None) => {
+                is_valid_nif(nif) && holder.appears_in(text, self.customer_labels())
+            }
+            (HolderId::Name(_), _) => holder.appears_in(text, self.customer_labels()),
+        }
+    }
+
+    fn customer_labels(&self) -> &'static [&'static str] {
+        CUSTOMER_LABELS
+    }
+
+    fn known_issuers(&self) -> &'static [KnownIssuer] {
+        KNOWN_UTILITIES
+    }
+
+    fn root_certificates(&self) -> &'static [&'static [u8]] {
+        ROOT_CERTIFICATES
+    }
+
+    fn postal_code(&self, text: &str) -> Option<String> {
+        extract_postal_code(text)
+    }
+
+    fn postal_prefix(&self, postal_code: &str) -> String {
+        // the CP4, which names the locality, the CP3 suffix narrows it down to a few streets
+        postal_code.chars().take(4).collect()
+    }
+
+    fn address(&self, text: &str) -> Option<Address> {
+        extract_address(text)
+    }
+
+    fn resolve_scope(&self, postal_code: &str, scope: Scope) -> Result<String, ResidencyError> {
+        // only the attribute disclosed for the scope leaves the prover, the postal code stays private
+        let location = match scope {
+            Scope::Country => "Portugal",
+            Scope::Region => region(postal_code).ok_or(ResidencyError::UnknownPostalCode)?,
+            Scope::City => {
+                municipality(postal_code)
+                    .ok_or(ResidencyError::UnknownPostalCode)?
+                    .concelho
+            }
+        };
+
+        Ok(location.to_string())
+    }
+
+    fn verify_qr_codes(
+        &self,
+        document_id: &str,
+        qr_codes: &[String],
+    ) -> Result<BTreeMap<String, String>, ResidencyError> {
+        let fiscal_codes: Vec<FiscalQrCode> = qr_codes
+            .iter()
+            .filter_map(|payload| FiscalQrCode::parse(payload))
+            .collect();
+        if fiscal_codes.is_empty() {
+            return Err(ResidencyError::NoQrCode);
+        }
+
+        // the ATCUD printed on the bill has to be the one registered in its QR code
+        fiscal_codes
+            .into_iter()
+            .find(|code| code.atcud() == Some(document_id))
+            .map(|code| code.fields)
+            .ok_or(ResidencyError::QrCodeMismatch)
+    }
+}
+
+/// Electricity, gas, water and telecommunications companies that bill households in Portugal.
+pub const KNOWN_UTILITIES: &[KnownIssuer] = &[
+    KnownIssuer { tax_id: "503504564", name: "EDP Comercial", category: IssuerCategory::Electricity },
+    KnownIssuer { tax_id: "504394029", name: "E-Redes", category: IssuerCategory::Electricity },
+    KnownIssuer { tax_id: "504499777", name: "Galp Energia", category: IssuerCategory::Gas },
+    KnownIssuer { tax_id: "500697370", name: "Petrogal", category: IssuerCategory::Gas },
+    KnownIssuer { tax_id: "980245974", name: "Endesa Energia", category: IssuerCategory::Electricity },
+    KnownIssuer { tax_id: "500906840", name: "EPAL", category: IssuerCategory::Water },
+    KnownIssuer { tax_id: "507718666", name: "Águas do Porto", category: IssuerCategory::Water },
+    KnownIssuer { tax_id: "504615947", name: "MEO", category: IssuerCategory::Telecom },
+    KnownIssuer { tax_id: "502604751", name: "NOS", category: IssuerCategory::Telecom },
+    KnownIssuer { tax_id: "502544180", name: "Vodafone Portugal", category: IssuerCategory::Telecom },
+];
+
+/// The roots of the State PKI (ECRaizEstado) and of the qualified trust service providers on the
+/// Portuguese trusted list, which utilities get their signing certificates from, as DER files
+/// included with `include_bytes!`.
+///
+/// None is pinned yet, so the program and the server do not require signed bills until their
+/// certificates are added here.
+pub const ROOT_CERTIFICATES: &[&[u8]] = &[];
+
+/// The labels of the customer's name and NIF on bills, some of them printed in English too.
+const CUSTOMER_LABELS: &[&str] = &[
+    "Cliente",
+    "Nome",
+    "Titular",
+    "NIF do cliente",
+    "NIF do adquirente",
+    "Contribuinte",
+    "Customer",
+];
+
+/// The NIF printed on invoices issued to a consumer who did not give their own.
+pub const ANONYMOUS_CONSUMER: &str = "999999990";
+
+/// Checks the mod-11 control digit of a Portuguese tax number (NIF or NIPC).
+pub fn is_valid_nif(nif: &str) -> bool {
+    let digits: Vec<u32> = nif.chars().filter_map(|c| c.to_digit(10)).collect();
+    if nif.len() != 9 || digits.len() != 9 || digits[0] == 0 {
+        return false;
+    }
+
+    let sum: u32 = digits[..8]
+        .iter()
+        .zip((2..=9).rev())
+        .map(|(d, weight)| d * weight)
+        .sum();
+    let check = match sum % 11 {
+        0 | 1 => 0,
+        r => 11 - r,
+    };
+
+    digits[8] == check
+}
+
+/// Finds the valid tax numbers printed after a NIF label, in the order they appear.
+pub fn extract_nifs(text: &str) -> Vec<String> {
+    // e.g. "NIF: 123456789", "NIPC PT 123456789", "Contribuinte n.º 123 456 789"
+    let re = Regex::new(r"(?i)(?:NIF|NIPC|Contribuinte)[^0-9\n]{0,12}?(?:PT\s?)?(\d{3}\s?\d{3}\s?\d{3})\b")
+        .unwrap();
+
+    re.captures_iter(text)
+        .map(|caps| caps[1].split_whitespace().collect::<String>())
+        .filter(|nif| is_valid_nif(nif))
+        .collect()
+}
+
+/// The fiscal QR code every Portuguese invoice must print, as specified by Portaria 195/2020.
+///
+/// The payload is a list of `code:value` fields separated by `*`, e.g.
+/// `A:123456789*B:999999990*...*H:ABC12345-0001*...`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FiscalQrCode {
+    pub fields: BTreeMap<String, String>,
+}
+
+impl FiscalQrCode {
+    /// Parses a QR code payload, which must at least carry the issuer NIF and the ATCUD.
+    pub fn parse(payload: &str) -> Option<Self> {
+        let fields: BTreeMap<String, String> = payload
+            .trim()
+            .split('*')
+            .map(|field| {
+                let (code, value) = field.split_once(':')?;
+                Some((code.to_string(), value.to_string()))
+            })
+            .collect::<Option<_>>()?;
+
+        (fields.contains_key("A") && fields.contains_key("H")).then_some(Self { fields })
+    }
+
+    pub fn issuer_nif(&self) -> Option<&str> {
+        self.field("A")
+    }
+
+    pub fn customer_nif(&self) -> Option<&str> {
+        self.field("B")
+    }
+
+    pub fn document_date(&self) -> Option<Date> {
+        self.field("F").and_then(parse_compact_date)
+    }
+
+    pub fn atcud(&self) -> Option<&str> {
+        self.field("H")
+    }
+
+    fn field(&self, code: &str) -> Option<&str> {
+        self.fields.get(code).map(String::as_str)
+    }
 }
 
 pub(super) fn is_valid_atcud(text: &str) -> Option<String> {
+    // the code printed by its label, other codes of the same shape (e.g. a contract number) may
+    // come before it
+    layout::labelled(text, &["ATCUD"], first_atcud).or_else(|| first_atcud(text))
+}
+
+fn first_atcud(text: &str) -> Option<String> {
     // ATCUD format: XXXXXXXX-Y+
     // where X is an 8-character series and Y is one or more digits
     let re = Regex::new(r"([A-Z0-9]{8}-\d+)").unwrap();
@@ -30,17 +260,147 @@ pub(super) fn is_valid_atcud(text: &str) -> Option<String> {
             // Verify series is exactly 8 characters
             series.len() == 8 && 
             // Verify sequence has at least 1 digit and all characters are digits
-            !sequence.is_empty() && sequence.chars().all(|c| c.is_digit(10))
+            !sequence.is_empty() && sequence.chars().all(|c| c.is_ascii_digit())
         });
 
         // TODO: validate from the government's API that the ATCUD is valid
         valid_atcud
 }
 
+/// Finds the issue date printed next to its label, e.g. "Data de emissão: 07/12/2024".
+pub fn extract_issue_date(text: &str) -> Option<Date> {
+    let re = Regex::new(
+        r"(?i)(?:Data\s+(?:de\s+)?emiss[ãa]o|Data\s+(?:da\s+)?fatura|Data|Date)\s*:?\s*(?:(\d{2})[/.-](\d{2})[/.-](\d{4})|(\d{4})-(\d{2})-(\d{2}))",
+    )
+    .unwrap();
+
+    let issue_date = re.captures_iter(text).find_map(|caps| {
+        let (year, month, day) = match caps.get(1) {
+            Some(day) => (&caps[3], &caps[2], day.as_str()),
+            None => (&caps[4], &caps[5], &caps[6]),
+        };
+        Date::new(year.parse().ok()?, month.parse().ok()?, day.parse().ok()?)
+    });
+
+    issue_date
+}
+
+// The QR code stores dates as YYYYMMDD
+fn parse_compact_date(date: &str) -> Option<Date> {
+    if date.len() != 8 || !date.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Date::new(date[..4].parse().ok()?, date[4..6].parse().ok()?, date[6..].parse().ok()?)
+}
+
+/// Finds the postal code of the address the bill is for: the one of the supply address printed
+/// by its label, or else the first one that is not of the issuer's own offices.
 pub(super) fn extract_postal_code(text: &str) -> Option<String> {
     // Portuguese postal code pattern: XXXX-XXX
     let postal_code_re = Regex::new(r"\b\d{4}-\d{3}\b").unwrap();
-    
-    postal_code_re.find(text)
-        .map(|m| m.as_str().to_string())
-}
\ No newline at end of file
+
+    if let Some(address) = layout::supply_address(text) {
+        return postal_code_re.find(&address).map(|m| m.as_str().to_string());
+    }
+    let headquarters = layout::headquarters(text);
+    let postal_code = postal_code_re
+        .find_iter(text)
+        .map(|m| m.as_str())
+        .find(|postal_code| !headquarters.contains(postal_code));
+    postal_code.map(str::to_string)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAYLOAD: &str = "A:501234560*B:123456789*C:PT*D:FT*E:N*F:20241207*G:FT 2024/001*H:ABC12345-0001*I1:PT*N:11.50*O:61.50*Q:abcd*R:9999";
+
+    #[test]
+    fn validates_nif_check_digit() {
+        assert!(is_valid_nif("501234560"));
+        assert!(is_valid_nif("123456789"));
+        assert!(!is_valid_nif("501234561"));
+        assert!(!is_valid_nif("012345678"));
+        assert!(!is_valid_nif("50123456"));
+        assert!(KNOWN_UTILITIES.iter().all(|issuer| is_valid_nif(issuer.tax_id)));
+    }
+
+    #[test]
+    fn extracts_labelled_nifs() {
+        let text = "EDP Comercial\nNIPC: 503 504 564\nCliente NIF PT123456789\nContribuinte n.º 501234561";
+        assert_eq!(extract_nifs(text), vec!["503504564", "123456789"]);
+    }
+
+    #[test]
+    fn extracts_issue_date() {
+        let expected = Date::new(2024, 12, 7);
+        assert_eq!(extract_issue_date("Data de emissão: 07/12/2024"), expected);
+        assert_eq!(extract_issue_date("Data de emissao 2024-12-07"), expected);
+        assert_eq!(extract_issue_date("Date: 07-12-2024"), expected);
+        assert_eq!(extract_issue_date("Data de vencimento\n31/02/2024"), None);
+    }
+
+    #[test]
+    fn prefers_labelled_fields_to_the_first_match() {
+        let text = "EDP Comercial\nAv. 24 de Julho, 12\n1249-300 Lisboa\nNIPC 503 504 564\n\
+                    Contrato: 20241234-01\nATCUD: JFR7K2QX-88213\n\
+                    Morada de fornecimento: Rua de Exemplo, 123, 4050-123 Porto";
+        assert_eq!(is_valid_atcud(text).as_deref(), Some("JFR7K2QX-88213"));
+        assert_eq!(extract_postal_code(text).as_deref(), Some("4050-123"));
+
+        // without a label, the issuer's own address is passed over
+        let text = text.replace("Morada de fornecimento: ", "");
+        assert_eq!(extract_postal_code(&text).as_deref(), Some("4050-123"));
+        assert_eq!(extract_postal_code("Sample Invoice\n4050-123 Porto").as_deref(), Some("4050-123"));
+        assert_eq!(extract_postal_code("NIPC 503 504 564\n1249-300 Lisboa"), None);
+    }
+
+    #[test]
+    fn customer_must_match_qr_code_nif() {
+        let qr_fields = FiscalQrCode::parse(PAYLOAD).unwrap().fields;
+        let text = "Cliente: Maria Santos\nNIF: 123456789";
+        let nif = |nif: &str| HolderId::TaxId(nif.to_string());
+
+        assert!(Portugal.is_customer(text, &qr_fields, &nif("123456789")));
+        assert!(!Portugal.is_customer("NIF: 501234560", &qr_fields, &nif("501234560")));
+        assert!(Portugal.is_customer(text, &qr_fields, &HolderId::Name("Maria Santos".to_string())));
+
+        // a bill to an anonymous consumer proves no NIF, one without a code only a labelled one
+        let mut anonymous = qr_fields.clone();
+        anonymous.insert("B".to_string(), ANONYMOUS_CONSUMER.to_string());
+        let text = "Cliente: Maria Santos\nNIF do cliente: 123456789";
+        assert!(!Portugal.is_customer(text, &anonymous, &nif("123456789")));
+        assert!(Portugal.is_customer(text, &BTreeMap::new(), &nif("123456789")));
+        assert!(!Portugal.is_customer("Referência: 123456789", &BTreeMap::new(), &nif("123456789")));
+        assert!(!Portugal.is_customer("Cliente: 123456780", &BTreeMap::new(), &nif("123456780")));
+        assert!(Portugal.is_customer(text, &anonymous, &HolderId::Name("Maria Santos".to_string())));
+        let greeting = "Olá Maria Santos, segue a sua fatura.";
+        assert!(!Portugal.is_customer(greeting, &anonymous, &HolderId::Name("Maria Santos".to_string())));
+    }
+
+    #[test]
+    fn parses_fiscal_qr_code() {
+        let code = FiscalQrCode::parse(PAYLOAD).unwrap();
+        assert_eq!(code.issuer_nif(), Some("501234560"));
+        assert_eq!(code.customer_nif(), Some("123456789"));
+        assert_eq!(code.document_date(), Date::new(2024, 12, 7));
+        assert_eq!(code.atcud(), Some("ABC12345-0001"));
+
+        assert_eq!(FiscalQrCode::parse("https://example.com"), None);
+        assert_eq!(FiscalQrCode::parse("A:501234560*B:123456789"), None);
+    }
+
+    #[test]
+    fn qr_code_must_match_atcud() {
+        let qr_codes = vec!["https://example.com".to_string(), PAYLOAD.to_string()];
+        assert!(Portugal.verify_qr_codes("ABC12345-0001", &qr_codes).is_ok());
+        assert_eq!(
+            Portugal.verify_qr_codes("XYZ98765-0001", &qr_codes),
+            Err(ResidencyError::QrCodeMismatch)
+        );
+        assert_eq!(
+            Portugal.verify_qr_codes("ABC12345-0001", &qr_codes[..1]),
+            Err(ResidencyError::NoQrCode)
+        );
+    }
+}
@@ -0,0 +1,204 @@
+// prover/lib/src/portugal/address.rs
+use super::layout;
+use crate::Address;
+use regex::Regex;
+
+/// Abbreviations of street types, spelled out when they start the street name.
+const STREET_TYPES: &[(&str, &str)] = &[
+    ("al", "alameda"),
+    ("av", "avenida"),
+    ("avda", "avenida"),
+    ("bc", "beco"),
+    ("cc", "calcada"),
+    ("calc", "calcada"),
+    ("estr", "estrada"),
+    ("lg", "largo"),
+    ("pc", "praca"),
+    ("pca", "praca"),
+    ("pct", "praceta"),
+    ("qta", "quinta"),
+    ("r", "rua"),
+    ("tv", "travessa"),
+    ("trav", "travessa"),
+    ("urb", "urbanizacao"),
+];
+
+/// Spellings of the side of a flat on its floor.
+const SIDES: &[(&str, &str)] = &[
+    ("esq", "esq"),
+    ("esquerdo", "esq"),
+    ("esquerda", "esq"),
+    ("dt", "dto"),
+    ("dta", "dto"),
+    ("dto", "dto"),
+    ("dir", "dto"),
+    ("direito", "dto"),
+    ("direita", "dto"),
+    ("fte", "frente"),
+    ("frente", "frente"),
+    ("tras", "tras"),
+    ("traseiras", "tras"),
+];
+
+/// Finds the address a bill was issued to: the supply address printed by its label, or else the
+/// first line with a postal code that is not of the issuer's own offices. The street is printed
+/// before the postal code on the same line, or on the line above.
+pub fn extract_address(text: &str) -> Option<Address> {
+    match layout::supply_address(text) {
+        Some(address) => address_in(&address, &[]),
+        None => address_in(text, layout::headquarters(text)),
+    }
+}
+
+fn address_in(text: &str, headquarters: &[&str]) -> Option<Address> {
+    let postal_code_re = Regex::new(r"\b(\d{4})\s?-\s?(\d{3})\b").unwrap();
+
+    let lines: Vec<&str> = text.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+    let (index, caps, postal_code) = lines.iter().enumerate().find_map(|(i, line)| {
+        let caps = postal_code_re.captures(line)?;
+        let postal_code = format!("{}-{}", &caps[1], &caps[2]);
+        (!headquarters.contains(&postal_code.as_str())).then_some((i, caps, postal_code))
+    })?;
+
+    // e.g. "Rua de Exemplo, 123, 4050-123 Porto", or the street alone on the line above
+    let before = lines[index][..caps.get(0)?.start()].trim_end_matches([',', ' ']);
+    let street_line = if before.chars().any(char::is_alphabetic) {
+        before
+    } else {
+        lines[..index].last()?
+    };
+
+    let (street, number, floor) = parse_street(&normalize(street_line))?;
+    Some(Address {
+        street,
+        number,
+        floor,
+        postal_code,
+    })
+}
+
+/// Lowercases the line, folds accents and spells out abbreviations, so that e.g. `Av. da
+/// Liberdade, n.º 110, 3.º Esq.` and `AVENIDA DA LIBERDADE 110 3º ESQUERDO` read the same.
+fn normalize(line: &str) -> String {
+    let ordinal_re = Regex::new(r"(\d+)\s*\.?\s*[ºª°]").unwrap();
+    let door_number_re = Regex::new(r"\bn\s*\.?\s*[ºo°]?\s*\.?\s*(\d)").unwrap();
+
+    // drop a label such as "Morada:"
+    let line = line.rsplit_once(':').map_or(line, |(_, address)| address);
+    let line = fold(&line.to_lowercase());
+    let line = door_number_re.replace_all(&line, " $1");
+    let line = ordinal_re.replace_all(&line, "${1}º ");
+    let line = line
+        .replace("r/c", " rc ")
+        .replace("res-do-chao", " rc ")
+        .replace("res do chao", " rc ")
+        .replace("s/n", " sn ");
+    let line = line.replace([',', ';', '.'], " ").replace(" - ", " ");
+
+    let mut words: Vec<&str> = line.split_whitespace().collect();
+    if let Some(first) = words.first_mut() {
+        if let Some((_, full)) = STREET_TYPES.iter().find(|(short, _)| short == first) {
+            *first = full;
+        }
+    }
+    words.join(" ")
+}
+
+// Splits a normalized street line into the street, door number and floor
+fn parse_street(line: &str) -> Option<(String, Option<String>, Option<String>)> {
+    let re = Regex::new(
+        r"^(?P<street>.+)\s+(?:(?P<number>\d+[a-z]?)|sn)(?:\s+(?P<floor>(?:\d+º|rc|cv|cave)(?:\s+andar)?(?:\s+[a-z]+)?))?$",
+    )
+    .unwrap();
+
+    let Some(caps) = re.captures(line) else {
+        // a street without door number
+        return line.chars().any(char::is_alphabetic).then(|| (line.to_string(), None, None));
+    };
+    let floor = caps.name("floor").map(|floor| {
+        floor
+            .as_str()
+            .split_whitespace()
+            .filter(|word| *word != "andar")
+            .map(|word| match word {
+                "cave" => "cv",
+                _ => SIDES
+                    .iter()
+                    .find(|(spelling, _)| *spelling == word)
+                    .map_or(word.trim_end_matches('º'), |(_, side)| side),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    });
+
+    let parts = (
+        caps["street"].to_string(),
+        caps.name("number").map(|number| number.as_str().to_string()),
+        floor,
+    );
+    Some(parts)
+}
+
+// Replaces the accented letters used in Portuguese by their base letter
+fn fold(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            'á' | 'à' | 'â' | 'ã' | 'ä' => 'a',
+            'é' | 'è' | 'ê' | 'ë' => 'e',
+            'í' | 'ì' | 'î' | 'ï' => 'i',
+            'ó' | 'ò' | 'ô' | 'õ' | 'ö' => 'o',
+            'ú' | 'ù' | 'û' | 'ü' => 'u',
+            'ç' => 'c',
+            _ => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(street: &str, number: Option<&str>, floor: Option<&str>, postal_code: &str) -> Address {
+        Address {
+            street: street.to_string(),
+            number: number.map(str::to_string),
+            floor: floor.map(str::to_string),
+            postal_code: postal_code.to_string(),
+        }
+    }
+
+    #[test]
+    fn extracts_street_number_and_postal_code() {
+        let text = "Billing Address:\nRua de Exemplo, 123\n4050-123 Porto, Portugal";
+        assert_eq!(
+            extract_address(text),
+            Some(address("rua de exemplo", Some("123"), None, "4050-123"))
+        );
+        assert_eq!(
+            extract_address("Morada: R. 25 de Abril 12A, 2800-001 Almada"),
+            Some(address("rua 25 de abril", Some("12a"), None, "2800-001"))
+        );
+        assert_eq!(extract_address("Sem morada"), None);
+    }
+
+    #[test]
+    fn normalizes_spellings_of_the_same_address() {
+        let expected = Some(address("avenida da liberdade", Some("110"), Some("3 esq"), "1250-146"));
+        for street in [
+            "Av. da Liberdade, n.º 110, 3.º Esq.",
+            "AVENIDA DA LIBERDADE 110 3º ESQUERDO",
+            "Avenida da Liberdade, nº110 - 3º andar esq",
+        ] {
+            assert_eq!(extract_address(&format!("{}\n1250-146 Lisboa", street)), expected, "{}", street);
+        }
+
+        assert_eq!(
+            extract_address("Praça do Comércio, 5, R/C Dto\n1100-148 Lisboa"),
+            Some(address("praca do comercio", Some("5"), Some("rc dto"), "1100-148"))
+        );
+        assert_eq!(
+            extract_address("Largo da Sé s/n\n4700-424 Braga"),
+            Some(address("largo da se", None, None, "4700-424"))
+        );
+    }
+}
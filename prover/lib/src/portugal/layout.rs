@@ -0,0 +1,288 @@
+// prover/lib/src/portugal/layout.rs
+//! Where fields are printed on a bill: the extracted text is split into cells at gaps of two
+//! spaces or more, so that a value can be found next to or under its label rather than by being
+//! the first text of its shape.
+//!
+//! The positions come from the PDF: [`crate::pdf::text`] follows the content stream of every
+//! page and writes each string at the column of its position, a column per five points, set
+//! apart from the string before it by two spaces at least. The column of a cell is then where it
+//! is printed. Text read otherwise, when the content stream cannot be followed, only lines up
+//! cells if the extractor pads them with spaces, as pdf-extract does. Without padding, a value is
+//! only found on the same line as its label or on the lines under it.
+use super::extract_nifs;
+use regex::Regex;
+
+/// Labels of the address the service is supplied at, which is where the customer lives. The
+/// address the bill is mailed to may be another one, and the issuer's own is printed too.
+const ADDRESS_LABELS: &[&str] = &[
+    "Morada de fornecimento",
+    "Local de fornecimento",
+    "Morada do local de consumo",
+    "Local de consumo",
+    "Morada de instalação",
+    "Local de instalação",
+];
+
+/// The most lines an address printed under its label spans.
+const MAX_ADDRESS_LINES: usize = 4;
+
+/// How an issuer lays out its bills.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Template {
+    pub tax_id: &'static str,
+    /// The labels of the supply address on the issuer's bills, tried before the usual ones.
+    pub address_labels: &'static [&'static str],
+    /// The postal codes of the issuer's own offices, printed in the header or footer of its
+    /// bills, which are never the customer's.
+    pub headquarters: &'static [&'static str],
+}
+
+impl Template {
+    /// The template of the known issuer whose tax number is printed on the bill, if any.
+    pub fn for_text(text: &str) -> Option<&'static Template> {
+        let nifs = extract_nifs(text);
+        TEMPLATES
+            .iter()
+            .find(|template| nifs.iter().any(|nif| nif == template.tax_id))
+    }
+}
+
+/// The layouts of the bills of [`super::KNOWN_UTILITIES`].
+pub const TEMPLATES: &[Template] = &[
+    Template { tax_id: "503504564", address_labels: &["Morada de fornecimento"], headquarters: &["1249-300"] },
+    Template { tax_id: "504394029", address_labels: &["Local de consumo"], headquarters: &["1050-044"] },
+    Template { tax_id: "504499777", address_labels: &["Local de consumo"], headquarters: &["1600-209"] },
+    Template { tax_id: "500697370", address_labels: &["Local de consumo"], headquarters: &["1600-209"] },
+    Template { tax_id: "500906840", address_labels: &["Local de consumo"], headquarters: &["1250-144"] },
+    Template { tax_id: "507718666", address_labels: &["Local de consumo"], headquarters: &["4300-367"] },
+    Template { tax_id: "504615947", address_labels: &["Morada de instalação"], headquarters: &["1069-300"] },
+    Template { tax_id: "502604751", address_labels: &["Morada de instalação"], headquarters: &["1600-404"] },
+    Template { tax_id: "502544180", address_labels: &["Morada de instalação"], headquarters: &["1998-017"] },
+];
+
+/// A run of text on a line, set apart from the rest of the line by a gap of two spaces or more,
+/// as a cell of a table or column would be when the extractor pads the text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Cell<'a> {
+    row: usize,
+    /// The number of characters before the first one of the cell on its line, which stands for
+    /// its position on the page.
+    column: usize,
+    text: &'a str,
+}
+
+impl Cell<'_> {
+    fn width(&self) -> usize {
+        self.text.chars().count()
+    }
+
+    // Cells overlap when some of their characters are printed at the same positions
+    fn is_under(&self, other: &Cell) -> bool {
+        self.column < other.column + other.width() && other.column < self.column + self.width()
+    }
+}
+
+/// The cells of the text of a bill, line by line.
+///
+/// Without padding every cell starts at column 0 of its line, so a value under its label is
+/// still found then, but not one in another column.
+struct Layout<'a> {
+    cells: Vec<Cell<'a>>,
+}
+
+impl<'a> Layout<'a> {
+    fn new(text: &'a str) -> Self {
+        let mut cells = Vec::new();
+        for (row, line) in text.lines().enumerate() {
+            let mut column = 0;
+            for (i, part) in line.split("  ").enumerate() {
+                // the separators, and the spaces beyond them, count towards the position
+                if i > 0 {
+                    column += 2;
+                }
+                if !part.trim().is_empty() {
+                    let leading = part.chars().take_while(|c| c.is_whitespace()).count();
+                    cells.push(Cell {
+                        row,
+                        column: column + leading,
+                        text: part.trim(),
+                    });
+                }
+                column += part.chars().count();
+            }
+        }
+        Self { cells }
+    }
+
+    /// The value printed for `label` for every place it appears: the rest of its cell, or else
+    /// the cell to its right, or else the cell under it. Each comes with the cell of the label
+    /// and the cell it is in.
+    fn values(&self, label: &str) -> Vec<(&Cell<'a>, &Cell<'a>, &'a str)> {
+        let re = Regex::new(&format!("(?i){}", regex::escape(label))).unwrap();
+        let mut values = Vec::new();
+        for (i, cell) in self.cells.iter().enumerate() {
+            let Some(found) = re.find(cell.text) else {
+                continue;
+            };
+            let rest = cell.text[found.end()..].trim_start_matches([':', ' ']);
+            let value = if !rest.is_empty() {
+                Some((cell, rest))
+            } else if let Some(right) = self.cells.get(i + 1).filter(|right| right.row == cell.row) {
+                Some((right, right.text))
+            } else {
+                self.below(cell).next().map(|below| (below, below.text))
+            };
+            values.extend(value.map(|(value_cell, value)| (cell, value_cell, value)));
+        }
+        values
+    }
+
+    // The cells under `cell` on the following rows, up to the first row with none
+    fn below<'b>(&'b self, cell: &'b Cell) -> impl Iterator<Item = &'b Cell<'a>> {
+        (cell.row + 1..).map_while(move |row| {
+            self.cells
+                .iter()
+                .find(|other| other.row == row && other.is_under(cell))
+        })
+    }
+
+    // Whether another field than `cell` starts on its row in the column of `label`
+    fn starts_field(&self, cell: &Cell, label: &Cell) -> bool {
+        self.cells
+            .iter()
+            .any(|other| other.row == cell.row && other.column == label.column && other != cell)
+    }
+}
+
+/// Finds the first value printed for one of `labels` that `parse` accepts.
+pub(super) fn labelled<T>(text: &str, labels: &[&str], parse: impl Fn(&str) -> Option<T>) -> Option<T> {
+    let layout = Layout::new(text);
+    labels
+        .iter()
+        .flat_map(|label| layout.values(label))
+        .find_map(|(_, _, value)| parse(value))
+}
+
+/// Finds the address the service of the bill is supplied at, as the lines printed after or
+/// under its label. Only an address with a postal code is returned.
+pub(super) fn supply_address(text: &str) -> Option<String> {
+    let postal_code_re = Regex::new(r"\b\d{4}-\d{3}\b").unwrap();
+    let layout = Layout::new(text);
+    let labels = Template::for_text(text)
+        .map_or(&[][..], |template| template.address_labels)
+        .iter()
+        .chain(ADDRESS_LABELS);
+
+    labels.flat_map(|label| layout.values(label)).find_map(|(label, first, value)| {
+        // the address goes on under its first line, until another field
+        let rest = layout
+            .below(first)
+            .take_while(|cell| !cell.text.contains(':') && !layout.starts_field(cell, label))
+            .map(|cell| cell.text);
+        let lines: Vec<&str> = std::iter::once(value).chain(rest).take(MAX_ADDRESS_LINES).collect();
+        let address = lines.join("\n");
+        postal_code_re.is_match(&address).then_some(address)
+    })
+}
+
+/// The postal codes of the issuer's offices, if the bill is from a known issuer.
+pub(super) fn headquarters(text: &str) -> &'static [&'static str] {
+    Template::for_text(text).map_or(&[], |template| template.headquarters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::portugal::KNOWN_UTILITIES;
+
+    #[test]
+    fn reads_cells_by_position() {
+        let layout = Layout::new("NIF: 1  ATCUD:\n   Rua X  AB-1\n\nFim");
+        let cell = |row, column, text| Cell { row, column, text };
+        assert_eq!(
+            layout.cells,
+            vec![cell(0, 0, "NIF: 1"), cell(0, 8, "ATCUD:"), cell(1, 3, "Rua X"), cell(1, 10, "AB-1"), cell(3, 0, "Fim")]
+        );
+
+        let values: Vec<&str> = layout.values("atcud").into_iter().map(|(_, _, value)| value).collect();
+        assert_eq!(values, vec!["AB-1"]);
+        let values: Vec<&str> = layout.values("NIF").into_iter().map(|(_, _, value)| value).collect();
+        assert_eq!(values, vec!["1"]);
+        let values: Vec<&str> = layout.values("Fim").into_iter().map(|(_, _, value)| value).collect();
+        assert!(values.is_empty());
+
+        // columns count characters, not bytes
+        let layout = Layout::new("Olá  \u{a0}Rua");
+        assert_eq!(layout.cells, vec![cell(0, 0, "Olá"), cell(0, 6, "Rua")]);
+    }
+
+    #[test]
+    fn finds_address_under_or_beside_its_label() {
+        let text = "Morada de fornecimento\nRua do Ouro, 12\n1100-060 Lisboa\n\nCPE: PT0002";
+        assert_eq!(supply_address(text).as_deref(), Some("Rua do Ouro, 12\n1100-060 Lisboa"));
+
+        let text = "Local de consumo:     Rua do Ouro, 12\n                      1100-060 Lisboa\nCPE:                  PT0002";
+        assert_eq!(supply_address(text).as_deref(), Some("Rua do Ouro, 12\n1100-060 Lisboa"));
+
+        // the label of a field without an address is passed over
+        let text = "Local de consumo: ver verso\nLocal de consumo: Rua do Ouro, 12, 1100-060 Lisboa";
+        assert_eq!(supply_address(text).as_deref(), Some("Rua do Ouro, 12, 1100-060 Lisboa"));
+        assert_eq!(supply_address("Morada: Rua do Ouro, 12, 1100-060 Lisboa"), None);
+    }
+
+    #[test]
+    fn finds_address_by_its_position_in_the_pdf() {
+        use lopdf::{dictionary, Document, Object, Stream};
+
+        // the supply address is printed under its label in the right column, the issuer's
+        // headquarters in the left one, each line drawn on its own
+        let lines = [
+            (50, 800, "Galp Energia"),
+            (320, 800, "Local de consumo:"),
+            (50, 786, "Rua Tomas da Fonseca"),
+            (320, 786, "Rua do Ouro, 12"),
+            (50, 772, "1600-209 Lisboa"),
+            (320, 772, "1100-060 Lisboa"),
+        ];
+        let content: String = lines
+            .iter()
+            .map(|(x, y, text)| format!("BT /F1 9 Tf {} {} Td ({}) Tj ET\n", x, y, text))
+            .collect();
+        let mut document = Document::with_version("1.5");
+        let font = document.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+        });
+        let contents = document.add_object(Stream::new(dictionary! {}, content.into_bytes()));
+        let pages = document.new_object_id();
+        let page = document.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages,
+            "Contents" => contents,
+            "Resources" => dictionary! { "Font" => dictionary! { "F1" => font } },
+        });
+        document.objects.insert(
+            pages,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page.into()],
+                "Count" => 1,
+            }),
+        );
+        let catalog = document.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages });
+        document.trailer.set("Root", catalog);
+
+        let text = crate::text::normalize(&crate::pdf::text(&document).unwrap());
+        assert_eq!(supply_address(&text).as_deref(), Some("Rua do Ouro, 12\n1100-060 Lisboa"));
+    }
+
+    #[test]
+    fn templates_are_for_known_issuers() {
+        assert!(TEMPLATES
+            .iter()
+            .all(|template| KNOWN_UTILITIES.iter().any(|issuer| issuer.tax_id == template.tax_id)));
+        assert_eq!(Template::for_text("MEO\nNIPC 504 615 947").map(|t| t.headquarters), Some(&["1069-300"][..]));
+        assert_eq!(Template::for_text("NIF: 123456789"), None);
+    }
+}
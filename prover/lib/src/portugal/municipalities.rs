@@ -0,0 +1,419 @@
+// prover/lib/src/portugal/municipalities.rs
+
+/// A Portuguese municipality (concelho) and the district it belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Municipality {
+    pub concelho: &'static str,
+    pub distrito: &'static str,
+}
+
+/// Resolves the municipality of a `XXXX-XXX` postal code from its CP4 prefix.
+pub fn municipality(postal_code: &str) -> Option<Municipality> {
+    let cp4: u16 = postal_code.get(..4)?.parse().ok()?;
+
+    let i = CP4_RANGES.partition_point(|&(_, end, _, _)| end < cp4);
+    CP4_RANGES
+        .get(i)
+        .filter(|&&(start, _, _, _)| start <= cp4)
+        .map(|&(_, _, concelho, distrito)| Municipality { concelho, distrito })
+}
+
+/// Resolves the district, or autonomous region, of a `XXXX-XXX` postal code.
+pub fn region(postal_code: &str) -> Option<&'static str> {
+    if let Some(municipality) = municipality(postal_code) {
+        return Some(municipality.distrito);
+    }
+
+    // every 9xxx prefix belongs to the islands, even where the concelho is not listed
+    let cp4: u16 = postal_code.get(..4)?.parse().ok()?;
+    match cp4 {
+        9000..=9499 => Some(MADEIRA),
+        9500..=9999 => Some(AZORES),
+        _ => None,
+    }
+}
+
+const MADEIRA: &str = "Região Autónoma da Madeira";
+const AZORES: &str = "Região Autónoma dos Açores";
+
+// Inclusive CP4 ranges assigned by CTT to each concelho, sorted and non-overlapping.
+// The autonomous regions of Madeira and the Azores take the place of the district.
+// Prefixes that are not covered here are not resolved.
+const CP4_RANGES: &[(u16, u16, &str, &str)] = &[
+    (1000, 1494, "Lisboa", "Lisboa"),
+    (1495, 1499, "Oeiras", "Lisboa"),
+    (1500, 1674, "Lisboa", "Lisboa"),
+    (1675, 1679, "Odivelas", "Lisboa"),
+    (1680, 1684, "Lisboa", "Lisboa"),
+    (1685, 1689, "Odivelas", "Lisboa"),
+    (1690, 1884, "Lisboa", "Lisboa"),
+    (1885, 1886, "Loures", "Lisboa"),
+    (1887, 1999, "Lisboa", "Lisboa"),
+    (2000, 2029, "Santarém", "Santarém"),
+    (2040, 2049, "Rio Maior", "Santarém"),
+    (2050, 2059, "Azambuja", "Lisboa"),
+    (2065, 2069, "Azambuja", "Lisboa"),
+    (2070, 2079, "Cartaxo", "Santarém"),
+    (2080, 2089, "Almeirim", "Santarém"),
+    (2090, 2099, "Alpiarça", "Santarém"),
+    (2100, 2109, "Coruche", "Santarém"),
+    (2120, 2129, "Salvaterra de Magos", "Santarém"),
+    (2130, 2139, "Benavente", "Santarém"),
+    (2140, 2149, "Chamusca", "Santarém"),
+    (2150, 2159, "Golegã", "Santarém"),
+    (2200, 2209, "Abrantes", "Santarém"),
+    (2240, 2249, "Ferreira do Zêzere", "Santarém"),
+    (2250, 2259, "Constância", "Santarém"),
+    (2260, 2269, "Vila Nova da Barquinha", "Santarém"),
+    (2300, 2309, "Tomar", "Santarém"),
+    (2330, 2339, "Entroncamento", "Santarém"),
+    (2350, 2359, "Torres Novas", "Santarém"),
+    (2380, 2389, "Alcanena", "Santarém"),
+    (2400, 2429, "Leiria", "Leiria"),
+    (2430, 2439, "Marinha Grande", "Leiria"),
+    (2440, 2449, "Batalha", "Leiria"),
+    (2450, 2459, "Nazaré", "Leiria"),
+    (2460, 2479, "Alcobaça", "Leiria"),
+    (2480, 2489, "Porto de Mós", "Leiria"),
+    (2490, 2499, "Ourém", "Santarém"),
+    (2500, 2509, "Caldas da Rainha", "Leiria"),
+    (2510, 2519, "Óbidos", "Leiria"),
+    (2520, 2529, "Peniche", "Leiria"),
+    (2530, 2539, "Lourinhã", "Lisboa"),
+    (2540, 2549, "Bombarral", "Leiria"),
+    (2550, 2559, "Cadaval", "Lisboa"),
+    (2560, 2569, "Torres Vedras", "Lisboa"),
+    (2580, 2589, "Alenquer", "Lisboa"),
+    (2590, 2599, "Sobral de Monte Agraço", "Lisboa"),
+    (2600, 2609, "Vila Franca de Xira", "Lisboa"),
+    (2610, 2614, "Amadora", "Lisboa"),
+    (2615, 2619, "Vila Franca de Xira", "Lisboa"),
+    (2620, 2624, "Odivelas", "Lisboa"),
+    (2625, 2629, "Vila Franca de Xira", "Lisboa"),
+    (2630, 2639, "Arruda dos Vinhos", "Lisboa"),
+    (2640, 2644, "Mafra", "Lisboa"),
+    (2650, 2654, "Amadora", "Lisboa"),
+    (2655, 2659, "Mafra", "Lisboa"),
+    (2660, 2664, "Loures", "Lisboa"),
+    (2665, 2669, "Mafra", "Lisboa"),
+    (2670, 2674, "Loures", "Lisboa"),
+    (2675, 2679, "Odivelas", "Lisboa"),
+    (2680, 2699, "Loures", "Lisboa"),
+    (2700, 2709, "Amadora", "Lisboa"),
+    (2710, 2719, "Sintra", "Lisboa"),
+    (2720, 2724, "Amadora", "Lisboa"),
+    (2725, 2729, "Sintra", "Lisboa"),
+    (2730, 2734, "Oeiras", "Lisboa"),
+    (2735, 2739, "Sintra", "Lisboa"),
+    (2740, 2744, "Oeiras", "Lisboa"),
+    (2745, 2749, "Sintra", "Lisboa"),
+    (2750, 2759, "Cascais", "Lisboa"),
+    (2760, 2764, "Oeiras", "Lisboa"),
+    (2765, 2769, "Cascais", "Lisboa"),
+    (2770, 2774, "Oeiras", "Lisboa"),
+    (2775, 2779, "Cascais", "Lisboa"),
+    (2780, 2784, "Oeiras", "Lisboa"),
+    (2785, 2789, "Cascais", "Lisboa"),
+    (2790, 2799, "Oeiras", "Lisboa"),
+    (2800, 2829, "Almada", "Setúbal"),
+    (2830, 2834, "Barreiro", "Setúbal"),
+    (2835, 2839, "Moita", "Setúbal"),
+    (2840, 2859, "Seixal", "Setúbal"),
+    (2860, 2864, "Moita", "Setúbal"),
+    (2865, 2869, "Seixal", "Setúbal"),
+    (2870, 2879, "Montijo", "Setúbal"),
+    (2890, 2899, "Alcochete", "Setúbal"),
+    (2900, 2919, "Setúbal", "Setúbal"),
+    (2950, 2969, "Palmela", "Setúbal"),
+    (2970, 2979, "Sesimbra", "Setúbal"),
+    (3000, 3049, "Coimbra", "Coimbra"),
+    (3050, 3059, "Mealhada", "Aveiro"),
+    (3060, 3069, "Cantanhede", "Coimbra"),
+    (3070, 3079, "Mira", "Coimbra"),
+    (3080, 3099, "Figueira da Foz", "Coimbra"),
+    (3100, 3109, "Pombal", "Leiria"),
+    (3130, 3139, "Soure", "Coimbra"),
+    (3140, 3149, "Montemor-o-Velho", "Coimbra"),
+    (3150, 3159, "Condeixa-a-Nova", "Coimbra"),
+    (3200, 3209, "Lousã", "Coimbra"),
+    (3220, 3229, "Miranda do Corvo", "Coimbra"),
+    (3230, 3239, "Penela", "Coimbra"),
+    (3240, 3249, "Ansião", "Leiria"),
+    (3250, 3259, "Alvaiázere", "Leiria"),
+    (3260, 3269, "Figueiró dos Vinhos", "Leiria"),
+    (3270, 3279, "Pedrógão Grande", "Leiria"),
+    (3280, 3289, "Castanheira de Pera", "Leiria"),
+    (3300, 3309, "Arganil", "Coimbra"),
+    (3320, 3329, "Pampilhosa da Serra", "Coimbra"),
+    (3330, 3339, "Góis", "Coimbra"),
+    (3350, 3359, "Vila Nova de Poiares", "Coimbra"),
+    (3360, 3369, "Penacova", "Coimbra"),
+    (3400, 3409, "Oliveira do Hospital", "Coimbra"),
+    (3420, 3429, "Tábua", "Coimbra"),
+    (3430, 3439, "Carregal do Sal", "Viseu"),
+    (3440, 3449, "Santa Comba Dão", "Viseu"),
+    (3450, 3459, "Mortágua", "Viseu"),
+    (3460, 3469, "Tondela", "Viseu"),
+    (3500, 3519, "Viseu", "Viseu"),
+    (3520, 3529, "Nelas", "Viseu"),
+    (3530, 3539, "Mangualde", "Viseu"),
+    (3550, 3559, "Penalva do Castelo", "Viseu"),
+    (3560, 3569, "Sátão", "Viseu"),
+    (3570, 3579, "Aguiar da Beira", "Guarda"),
+    (3600, 3609, "Castro Daire", "Viseu"),
+    (3620, 3629, "Moimenta da Beira", "Viseu"),
+    (3640, 3649, "Sernancelhe", "Viseu"),
+    (3650, 3659, "Vila Nova de Paiva", "Viseu"),
+    (3660, 3669, "São Pedro do Sul", "Viseu"),
+    (3670, 3679, "Vouzela", "Viseu"),
+    (3680, 3689, "Oliveira de Frades", "Viseu"),
+    (3700, 3709, "São João da Madeira", "Aveiro"),
+    (3720, 3729, "Oliveira de Azeméis", "Aveiro"),
+    (3730, 3739, "Vale de Cambra", "Aveiro"),
+    (3740, 3749, "Sever do Vouga", "Aveiro"),
+    (3750, 3759, "Águeda", "Aveiro"),
+    (3770, 3779, "Oliveira do Bairro", "Aveiro"),
+    (3780, 3789, "Anadia", "Aveiro"),
+    (3800, 3819, "Aveiro", "Aveiro"),
+    (3830, 3839, "Ílhavo", "Aveiro"),
+    (3840, 3849, "Vagos", "Aveiro"),
+    (3850, 3859, "Albergaria-a-Velha", "Aveiro"),
+    (3860, 3869, "Estarreja", "Aveiro"),
+    (3870, 3879, "Murtosa", "Aveiro"),
+    (3880, 3889, "Ovar", "Aveiro"),
+    (4000, 4369, "Porto", "Porto"),
+    (4400, 4419, "Vila Nova de Gaia", "Porto"),
+    (4420, 4429, "Gondomar", "Porto"),
+    (4430, 4434, "Vila Nova de Gaia", "Porto"),
+    (4435, 4439, "Gondomar", "Porto"),
+    (4440, 4449, "Valongo", "Porto"),
+    (4450, 4469, "Matosinhos", "Porto"),
+    (4470, 4479, "Maia", "Porto"),
+    (4480, 4489, "Vila do Conde", "Porto"),
+    (4490, 4499, "Póvoa de Varzim", "Porto"),
+    (4500, 4509, "Espinho", "Aveiro"),
+    (4510, 4519, "Gondomar", "Porto"),
+    (4520, 4529, "Santa Maria da Feira", "Aveiro"),
+    (4535, 4539, "Santa Maria da Feira", "Aveiro"),
+    (4540, 4549, "Arouca", "Aveiro"),
+    (4550, 4559, "Castelo de Paiva", "Aveiro"),
+    (4560, 4569, "Penafiel", "Porto"),
+    (4580, 4589, "Paredes", "Porto"),
+    (4590, 4599, "Paços de Ferreira", "Porto"),
+    (4600, 4609, "Amarante", "Porto"),
+    (4610, 4619, "Felgueiras", "Porto"),
+    (4620, 4629, "Lousada", "Porto"),
+    (4630, 4639, "Marco de Canaveses", "Porto"),
+    (4660, 4669, "Resende", "Viseu"),
+    (4690, 4699, "Cinfães", "Viseu"),
+    (4700, 4719, "Braga", "Braga"),
+    (4720, 4729, "Amares", "Braga"),
+    (4730, 4739, "Vila Verde", "Braga"),
+    (4740, 4749, "Esposende", "Braga"),
+    (4750, 4759, "Barcelos", "Braga"),
+    (4760, 4779, "Vila Nova de Famalicão", "Braga"),
+    (4780, 4784, "Santo Tirso", "Porto"),
+    (4785, 4789, "Trofa", "Porto"),
+    (4800, 4814, "Guimarães", "Braga"),
+    (4815, 4819, "Vizela", "Braga"),
+    (4820, 4829, "Fafe", "Braga"),
+    (4830, 4834, "Póvoa de Lanhoso", "Braga"),
+    (4835, 4839, "Guimarães", "Braga"),
+    (4840, 4849, "Terras de Bouro", "Braga"),
+    (4850, 4859, "Vieira do Minho", "Braga"),
+    (4860, 4869, "Cabeceiras de Basto", "Braga"),
+    (4870, 4879, "Ribeira de Pena", "Vila Real"),
+    (4880, 4889, "Mondim de Basto", "Vila Real"),
+    (4890, 4899, "Celorico de Basto", "Braga"),
+    (4900, 4909, "Viana do Castelo", "Viana do Castelo"),
+    (4910, 4919, "Caminha", "Viana do Castelo"),
+    (4920, 4924, "Vila Nova de Cerveira", "Viana do Castelo"),
+    (4925, 4929, "Viana do Castelo", "Viana do Castelo"),
+    (4930, 4934, "Valença", "Viana do Castelo"),
+    (4935, 4939, "Viana do Castelo", "Viana do Castelo"),
+    (4940, 4949, "Paredes de Coura", "Viana do Castelo"),
+    (4950, 4959, "Monção", "Viana do Castelo"),
+    (4960, 4969, "Melgaço", "Viana do Castelo"),
+    (4970, 4979, "Arcos de Valdevez", "Viana do Castelo"),
+    (4980, 4989, "Ponte da Barca", "Viana do Castelo"),
+    (4990, 4999, "Ponte de Lima", "Viana do Castelo"),
+    (5000, 5009, "Vila Real", "Vila Real"),
+    (5030, 5039, "Santa Marta de Penaguião", "Vila Real"),
+    (5040, 5049, "Mesão Frio", "Vila Real"),
+    (5050, 5059, "Peso da Régua", "Vila Real"),
+    (5060, 5069, "Sabrosa", "Vila Real"),
+    (5070, 5079, "Alijó", "Vila Real"),
+    (5085, 5089, "Alijó", "Vila Real"),
+    (5090, 5099, "Murça", "Vila Real"),
+    (5100, 5109, "Lamego", "Viseu"),
+    (5140, 5149, "Carrazeda de Ansiães", "Bragança"),
+    (5150, 5159, "Vila Nova de Foz Côa", "Guarda"),
+    (5160, 5169, "Torre de Moncorvo", "Bragança"),
+    (5180, 5189, "Freixo de Espada à Cinta", "Bragança"),
+    (5200, 5209, "Mogadouro", "Bragança"),
+    (5210, 5219, "Miranda do Douro", "Bragança"),
+    (5230, 5239, "Vimioso", "Bragança"),
+    (5300, 5309, "Bragança", "Bragança"),
+    (5320, 5329, "Vinhais", "Bragança"),
+    (5340, 5349, "Macedo de Cavaleiros", "Bragança"),
+    (5350, 5359, "Alfândega da Fé", "Bragança"),
+    (5360, 5369, "Vila Flor", "Bragança"),
+    (5370, 5379, "Mirandela", "Bragança"),
+    (5400, 5409, "Chaves", "Vila Real"),
+    (5430, 5439, "Valpaços", "Vila Real"),
+    (5450, 5459, "Vila Pouca de Aguiar", "Vila Real"),
+    (5460, 5469, "Boticas", "Vila Real"),
+    (5470, 5479, "Montalegre", "Vila Real"),
+    (6000, 6009, "Castelo Branco", "Castelo Branco"),
+    (6030, 6039, "Vila Velha de Ródão", "Castelo Branco"),
+    (6040, 6049, "Gavião", "Portalegre"),
+    (6050, 6059, "Nisa", "Portalegre"),
+    (6060, 6069, "Idanha-a-Nova", "Castelo Branco"),
+    (6090, 6099, "Penamacor", "Castelo Branco"),
+    (6100, 6109, "Sertã", "Castelo Branco"),
+    (6110, 6119, "Vila de Rei", "Castelo Branco"),
+    (6120, 6129, "Mação", "Santarém"),
+    (6150, 6159, "Proença-a-Nova", "Castelo Branco"),
+    (6160, 6169, "Oleiros", "Castelo Branco"),
+    (6200, 6229, "Covilhã", "Castelo Branco"),
+    (6230, 6239, "Fundão", "Castelo Branco"),
+    (6250, 6259, "Belmonte", "Castelo Branco"),
+    (6260, 6269, "Manteigas", "Guarda"),
+    (6270, 6279, "Seia", "Guarda"),
+    (6290, 6299, "Gouveia", "Guarda"),
+    (6300, 6309, "Guarda", "Guarda"),
+    (6320, 6329, "Sabugal", "Guarda"),
+    (6350, 6359, "Almeida", "Guarda"),
+    (6360, 6369, "Celorico da Beira", "Guarda"),
+    (6400, 6409, "Pinhel", "Guarda"),
+    (6420, 6429, "Trancoso", "Guarda"),
+    (6430, 6439, "Mêda", "Guarda"),
+    (6440, 6449, "Figueira de Castelo Rodrigo", "Guarda"),
+    (7000, 7009, "Évora", "Évora"),
+    (7040, 7049, "Arraiolos", "Évora"),
+    (7050, 7059, "Montemor-o-Novo", "Évora"),
+    (7080, 7089, "Vendas Novas", "Évora"),
+    (7090, 7099, "Viana do Alentejo", "Évora"),
+    (7100, 7109, "Estremoz", "Évora"),
+    (7150, 7159, "Borba", "Évora"),
+    (7160, 7169, "Vila Viçosa", "Évora"),
+    (7170, 7179, "Redondo", "Évora"),
+    (7200, 7209, "Reguengos de Monsaraz", "Évora"),
+    (7220, 7229, "Portel", "Évora"),
+    (7230, 7239, "Barrancos", "Beja"),
+    (7240, 7249, "Mourão", "Évora"),
+    (7250, 7259, "Alandroal", "Évora"),
+    (7300, 7309, "Portalegre", "Portalegre"),
+    (7320, 7329, "Castelo de Vide", "Portalegre"),
+    (7330, 7339, "Marvão", "Portalegre"),
+    (7340, 7349, "Arronches", "Portalegre"),
+    (7350, 7359, "Elvas", "Portalegre"),
+    (7370, 7379, "Campo Maior", "Portalegre"),
+    (7400, 7409, "Ponte de Sor", "Portalegre"),
+    (7430, 7439, "Crato", "Portalegre"),
+    (7440, 7449, "Alter do Chão", "Portalegre"),
+    (7450, 7459, "Monforte", "Portalegre"),
+    (7460, 7469, "Fronteira", "Portalegre"),
+    (7470, 7479, "Sousel", "Portalegre"),
+    (7480, 7489, "Avis", "Portalegre"),
+    (7490, 7499, "Mora", "Évora"),
+    (7500, 7509, "Santiago do Cacém", "Setúbal"),
+    (7520, 7529, "Sines", "Setúbal"),
+    (7540, 7549, "Santiago do Cacém", "Setúbal"),
+    (7570, 7579, "Grândola", "Setúbal"),
+    (7580, 7589, "Alcácer do Sal", "Setúbal"),
+    (7600, 7609, "Aljustrel", "Beja"),
+    (7630, 7639, "Odemira", "Beja"),
+    (7670, 7679, "Ourique", "Beja"),
+    (7700, 7709, "Almodôvar", "Beja"),
+    (7750, 7759, "Mértola", "Beja"),
+    (7780, 7789, "Castro Verde", "Beja"),
+    (7800, 7809, "Beja", "Beja"),
+    (7830, 7839, "Serpa", "Beja"),
+    (7860, 7869, "Moura", "Beja"),
+    (7900, 7909, "Ferreira do Alentejo", "Beja"),
+    (7920, 7929, "Alvito", "Beja"),
+    (7940, 7949, "Cuba", "Beja"),
+    (7960, 7969, "Vidigueira", "Beja"),
+    (8000, 8009, "Faro", "Faro"),
+    (8100, 8109, "Loulé", "Faro"),
+    (8125, 8129, "Loulé", "Faro"),
+    (8135, 8139, "Loulé", "Faro"),
+    (8150, 8159, "São Brás de Alportel", "Faro"),
+    (8200, 8209, "Albufeira", "Faro"),
+    (8300, 8309, "Silves", "Faro"),
+    (8365, 8369, "Silves", "Faro"),
+    (8400, 8409, "Lagoa", "Faro"),
+    (8500, 8509, "Portimão", "Faro"),
+    (8550, 8559, "Monchique", "Faro"),
+    (8600, 8609, "Lagos", "Faro"),
+    (8650, 8659, "Vila do Bispo", "Faro"),
+    (8670, 8679, "Aljezur", "Faro"),
+    (8700, 8709, "Olhão", "Faro"),
+    (8800, 8809, "Tavira", "Faro"),
+    (8900, 8909, "Vila Real de Santo António", "Faro"),
+    (8950, 8959, "Castro Marim", "Faro"),
+    (8970, 8979, "Alcoutim", "Faro"),
+    (9000, 9099, "Funchal", MADEIRA),
+    (9100, 9109, "Santa Cruz", MADEIRA),
+    (9125, 9129, "Santa Cruz", MADEIRA),
+    (9135, 9139, "Santa Cruz", MADEIRA),
+    (9200, 9209, "Machico", MADEIRA),
+    (9230, 9239, "Santana", MADEIRA),
+    (9240, 9249, "São Vicente", MADEIRA),
+    (9270, 9279, "Porto Moniz", MADEIRA),
+    (9300, 9309, "Câmara de Lobos", MADEIRA),
+    (9350, 9359, "Ribeira Brava", MADEIRA),
+    (9360, 9369, "Ponta do Sol", MADEIRA),
+    (9370, 9379, "Calheta", MADEIRA),
+    (9400, 9409, "Porto Santo", MADEIRA),
+    (9500, 9509, "Ponta Delgada", AZORES),
+    (9560, 9569, "Lagoa", AZORES),
+    (9580, 9589, "Vila do Porto", AZORES),
+    (9600, 9609, "Ribeira Grande", AZORES),
+    (9650, 9659, "Povoação", AZORES),
+    (9675, 9679, "Povoação", AZORES),
+    (9680, 9689, "Vila Franca do Campo", AZORES),
+    (9700, 9709, "Angra do Heroísmo", AZORES),
+    (9760, 9769, "Praia da Vitória", AZORES),
+    (9800, 9809, "Velas", AZORES),
+    (9850, 9859, "Calheta", AZORES),
+    (9880, 9889, "Santa Cruz da Graciosa", AZORES),
+    (9900, 9909, "Horta", AZORES),
+    (9930, 9939, "Lajes do Pico", AZORES),
+    (9940, 9949, "São Roque do Pico", AZORES),
+    (9950, 9959, "Madalena", AZORES),
+    (9960, 9969, "Lajes das Flores", AZORES),
+    (9970, 9979, "Santa Cruz das Flores", AZORES),
+    (9980, 9989, "Corvo", AZORES),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_are_sorted_and_disjoint() {
+        for pair in CP4_RANGES.windows(2) {
+            assert!(pair[0].0 <= pair[0].1);
+            assert!(pair[0].1 < pair[1].0, "{:?} overlaps {:?}", pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn resolves_cp4_prefix() {
+        assert_eq!(municipality("4050-123"), Some(Municipality { concelho: "Porto", distrito: "Porto" }));
+        assert_eq!(municipality("2795-001").map(|m| m.concelho), Some("Oeiras"));
+        assert_eq!(municipality("9500-150").map(|m| m.distrito), Some("Região Autónoma dos Açores"));
+        assert_eq!(municipality("3990-001"), None);
+    }
+
+    #[test]
+    fn resolves_district_or_autonomous_region() {
+        assert_eq!(region("4050-123"), Some("Porto"));
+        assert_eq!(region("9000-018"), Some(MADEIRA));
+        // not a listed concelho, but still in the Azores
+        assert_eq!(region("9555-001"), Some(AZORES));
+        assert_eq!(region("3990-001"), None);
+    }
+}
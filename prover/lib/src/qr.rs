@@ -0,0 +1,638 @@
+// prover/lib/src/qr.rs
+//! A decoder for the QR codes that invoicing software embeds in bills.
+//!
+//! Codes are expected as a program renders them: upright, not skewed, with every module drawn as
+//! a square of whole pixels and nothing but the quiet zone around them. Photos or scans of
+//! printed codes are not supported.
+
+/// An 8-bit grayscale bitmap, stored row by row from the top-left corner.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrayImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+/// Decodes the QR code drawn on `image` and returns its text.
+pub fn decode(image: &GrayImage) -> Option<String> {
+    let grid = sample_grid(image)?;
+    let bytes = decode_grid(&grid)?;
+
+    // payloads are UTF-8 in practice, anything else is read as Latin-1
+    Some(match String::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(e) => e.into_bytes().iter().map(|&b| b as char).collect(),
+    })
+}
+
+/// The modules of a QR code, `true` for dark.
+struct Grid {
+    size: usize,
+    modules: Vec<bool>,
+}
+
+impl Grid {
+    fn get(&self, x: usize, y: usize) -> bool {
+        self.modules[y * self.size + x]
+    }
+}
+
+fn sample_grid(image: &GrayImage) -> Option<Grid> {
+    let GrayImage { width, height, ref pixels } = *image;
+    if width == 0 || height == 0 || pixels.len() != width * height {
+        return None;
+    }
+
+    let min = *pixels.iter().min()?;
+    let max = *pixels.iter().max()?;
+    if max - min < 64 {
+        return None;
+    }
+    let threshold = (u16::from(min) + u16::from(max)) / 2;
+    let dark = |x: usize, y: usize| u16::from(pixels[y * width + x]) < threshold;
+
+    // the bounding box of the dark pixels is the code itself, without its quiet zone
+    let (mut left, mut top, mut right, mut bottom) = (usize::MAX, usize::MAX, 0, 0);
+    for y in 0..height {
+        for x in 0..width {
+            if dark(x, y) {
+                left = left.min(x);
+                top = top.min(y);
+                right = right.max(x);
+                bottom = bottom.max(y);
+            }
+        }
+    }
+    if left > right {
+        return None;
+    }
+    let (code_width, code_height) = (right - left + 1, bottom - top + 1);
+
+    // the top-left finder pattern is 7 modules wide, which gives the module size
+    let finder = (left..=right).take_while(|&x| dark(x, top)).count();
+    if finder < 7 || top + finder > bottom + 1 || !is_finder_pattern(dark, left, top, finder) {
+        return None;
+    }
+    let modules = (code_width * 7 + finder / 2) / finder;
+    let version = (modules.saturating_sub(17) + 2) / 4;
+    if !(1..=40).contains(&version) {
+        return None;
+    }
+    let size = version * 4 + 17;
+    if code_width < size || code_height.abs_diff(code_width) > code_width / size {
+        return None;
+    }
+
+    // read every module at its center
+    let mut modules = Vec::with_capacity(size * size);
+    for row in 0..size {
+        let y = top + (2 * row + 1) * code_height / (2 * size);
+        for col in 0..size {
+            let x = left + (2 * col + 1) * code_width / (2 * size);
+            modules.push(dark(x, y));
+        }
+    }
+
+    Some(Grid { size, modules })
+}
+
+// A finder pattern is a dark square ring, a light ring and a dark center, 1:1:3:1:1 modules
+// across. Anything else in the corner, e.g. a logo, is not a code.
+fn is_finder_pattern(dark: impl Fn(usize, usize) -> bool, left: usize, top: usize, width: usize) -> bool {
+    let (right, bottom) = (left + width - 1, top + width - 1);
+    let ring = (0..width).all(|i| {
+        dark(left + i, top) && dark(left + i, bottom) && dark(left, top + i) && dark(right, top + i)
+    });
+    let module = width / 7;
+    let (light, center) = (module + module / 2, width / 2);
+    ring && !dark(left + light, top + light)
+        && !dark(right - light, bottom - light)
+        && dark(left + center, top + center)
+}
+
+fn decode_grid(grid: &Grid) -> Option<Vec<u8>> {
+    let version = (grid.size - 17) / 4;
+    let (ecl, mask) = read_format(grid)?;
+    let codewords = read_codewords(grid, version, mask);
+    let data = correct_blocks(&codewords, version, ecl)?;
+
+    parse_segments(&data, version)
+}
+
+// Returns the error correction level, as an index into the block tables, and the mask pattern.
+fn read_format(grid: &Grid) -> Option<(usize, u8)> {
+    let size = grid.size;
+    let bit = |x: usize, y: usize| u32::from(grid.get(x, y));
+
+    // the format is stored twice: around the top-left finder, and split between the other two
+    let mut first = 0;
+    for i in 0..=5 {
+        first |= bit(8, i) << i;
+    }
+    first |= bit(8, 7) << 6;
+    first |= bit(8, 8) << 7;
+    first |= bit(7, 8) << 8;
+    for i in 9..15 {
+        first |= bit(14 - i, 8) << i;
+    }
+
+    let mut second = 0;
+    for i in 0..8 {
+        second |= bit(size - 1 - i, 8) << i;
+    }
+    for i in 8..15 {
+        second |= bit(8, size - 15 + i) << i;
+    }
+
+    // there are only 32 valid formats, take the closest one to either copy
+    let (data, distance) = (0..32u32)
+        .map(|data| {
+            let bits = format_bits(data);
+            let distance = (bits ^ first).count_ones().min((bits ^ second).count_ones());
+            (data, distance)
+        })
+        .min_by_key(|&(_, distance)| distance)?;
+    if distance > 3 {
+        return None;
+    }
+
+    let ecl = match data >> 3 {
+        1 => 0, // L
+        0 => 1, // M
+        3 => 2, // Q
+        _ => 3, // H
+    };
+    Some((ecl, (data & 7) as u8))
+}
+
+fn format_bits(data: u32) -> u32 {
+    let mut rem = data;
+    for _ in 0..10 {
+        rem = (rem << 1) ^ ((rem >> 9) * 0x537);
+    }
+    ((data << 10) | rem) ^ 0x5412
+}
+
+fn read_codewords(grid: &Grid, version: usize, mask: u8) -> Vec<u8> {
+    let size = grid.size;
+    let function = function_modules(version);
+    let mut codewords = vec![0u8; num_raw_data_modules(version) / 8];
+    let total_bits = codewords.len() * 8;
+
+    // codewords zigzag in two-module columns from the bottom-right corner, skipping the
+    // vertical timing pattern
+    let mut i = 0;
+    let mut right = size - 1;
+    loop {
+        if right == 6 {
+            right = 5;
+        }
+        let upward = (right + 1) & 2 == 0;
+        for vert in 0..size {
+            let y = if upward { size - 1 - vert } else { vert };
+            for x in [right, right - 1] {
+                if !function[y * size + x] && i < total_bits {
+                    if grid.get(x, y) ^ mask_bit(mask, x, y) {
+                        codewords[i >> 3] |= 0x80 >> (i & 7);
+                    }
+                    i += 1;
+                }
+            }
+        }
+        if right < 2 {
+            break;
+        }
+        right -= 2;
+    }
+
+    codewords
+}
+
+fn mask_bit(mask: u8, x: usize, y: usize) -> bool {
+    let value = match mask {
+        0 => (x + y) % 2,
+        1 => y % 2,
+        2 => x % 3,
+        3 => (x + y) % 3,
+        4 => (x / 3 + y / 2) % 2,
+        5 => x * y % 2 + x * y % 3,
+        6 => (x * y % 2 + x * y % 3) % 2,
+        _ => ((x + y) % 2 + x * y % 3) % 2,
+    };
+    value == 0
+}
+
+// Marks the modules that belong to finder, timing, alignment, format and version patterns.
+fn function_modules(version: usize) -> Vec<bool> {
+    let size = version * 4 + 17;
+    let mut function = vec![false; size * size];
+    let mut mark = |x: usize, y: usize| function[y * size + x] = true;
+
+    for i in 0..size {
+        mark(6, i);
+        mark(i, 6);
+    }
+
+    // finders with their separators, and the format information next to them
+    for a in 0..9 {
+        for b in 0..8 {
+            mark(a, b);
+            mark(size - 8 + b, a);
+            mark(a, size - 8 + b);
+        }
+        mark(a, 8);
+    }
+
+    let positions = alignment_positions(version);
+    if let Some(last) = positions.len().checked_sub(1) {
+        for (i, &ay) in positions.iter().enumerate() {
+            for (j, &ax) in positions.iter().enumerate() {
+                if (i == 0 && (j == 0 || j == last)) || (i == last && j == 0) {
+                    continue;
+                }
+                for dy in 0..5 {
+                    for dx in 0..5 {
+                        mark(ax - 2 + dx, ay - 2 + dy);
+                    }
+                }
+            }
+        }
+    }
+
+    if version >= 7 {
+        for i in 0..18 {
+            let (a, b) = (size - 11 + i % 3, i / 3);
+            mark(a, b);
+            mark(b, a);
+        }
+    }
+
+    function
+}
+
+fn alignment_positions(version: usize) -> Vec<usize> {
+    if version == 1 {
+        return vec![];
+    }
+
+    let size = version * 4 + 17;
+    let count = version / 7 + 2;
+    let step = if version == 32 {
+        26
+    } else {
+        (version * 4 + count * 2 + 1) / (count * 2 - 2) * 2
+    };
+
+    let mut positions: Vec<usize> = (0..count - 1).map(|i| size - 7 - i * step).collect();
+    positions.push(6);
+    positions.reverse();
+    positions
+}
+
+fn num_raw_data_modules(version: usize) -> usize {
+    let mut result = (16 * version + 128) * version + 64;
+    if version >= 2 {
+        let count = version / 7 + 2;
+        result -= (25 * count - 10) * count - 55;
+        if version >= 7 {
+            result -= 36;
+        }
+    }
+    result
+}
+
+fn correct_blocks(codewords: &[u8], version: usize, ecl: usize) -> Option<Vec<u8>> {
+    let num_blocks = NUM_ERROR_CORRECTION_BLOCKS[ecl][version];
+    let ecc_len = ECC_CODEWORDS_PER_BLOCK[ecl][version];
+    let num_short = num_blocks - codewords.len() % num_blocks;
+    let short_len = codewords.len() / num_blocks;
+
+    // undo the interleaving, the short blocks have no codeword at the end of the data
+    let mut blocks = vec![Vec::with_capacity(short_len + 1); num_blocks];
+    let mut codewords = codewords.iter();
+    for i in 0..=short_len {
+        for (j, block) in blocks.iter_mut().enumerate() {
+            if i != short_len - ecc_len || j >= num_short {
+                block.push(*codewords.next()?);
+            }
+        }
+    }
+
+    let mut data = Vec::new();
+    for mut block in blocks {
+        reed_solomon::correct(&mut block, ecc_len)?;
+        data.extend_from_slice(&block[..block.len() - ecc_len]);
+    }
+    Some(data)
+}
+
+fn parse_segments(data: &[u8], version: usize) -> Option<Vec<u8>> {
+    const ALPHANUMERIC: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+    let class = match version {
+        1..=9 => 0,
+        10..=26 => 1,
+        _ => 2,
+    };
+    let mut reader = BitReader { data, position: 0 };
+    let mut text = Vec::new();
+
+    while reader.remaining() >= 4 {
+        match reader.read(4)? {
+            0 => break,
+            1 => {
+                let mut count = reader.read([10, 12, 14][class])?;
+                while count > 0 {
+                    let digits = count.min(3);
+                    let value = reader.read([0, 4, 7, 10][digits as usize])?;
+                    if value >= 10u32.pow(digits) {
+                        return None;
+                    }
+                    text.extend(format!("{:0width$}", value, width = digits as usize).bytes());
+                    count -= digits;
+                }
+            }
+            2 => {
+                let mut count = reader.read([9, 11, 13][class])?;
+                while count >= 2 {
+                    let value = reader.read(11)? as usize;
+                    text.push(*ALPHANUMERIC.get(value / 45)?);
+                    text.push(ALPHANUMERIC[value % 45]);
+                    count -= 2;
+                }
+                if count == 1 {
+                    text.push(*ALPHANUMERIC.get(reader.read(6)? as usize)?);
+                }
+            }
+            4 => {
+                let count = reader.read([8, 16, 16][class])?;
+                for _ in 0..count {
+                    text.push(reader.read(8)? as u8);
+                }
+            }
+            7 => {
+                // an ECI designator, the payload is read as UTF-8 regardless
+                let first = reader.read(8)?;
+                if first & 0x80 != 0 {
+                    reader.read(if first & 0x40 == 0 { 8 } else { 16 })?;
+                }
+            }
+            // Kanji and structured append are not used on invoices
+            _ => return None,
+        }
+    }
+
+    Some(text)
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl BitReader<'_> {
+    fn remaining(&self) -> usize {
+        self.data.len() * 8 - self.position
+    }
+
+    fn read(&mut self, bits: usize) -> Option<u32> {
+        if bits > self.remaining() {
+            return None;
+        }
+        let value = (self.position..self.position + bits).fold(0, |value, i| {
+            (value << 1) | u32::from((self.data[i >> 3] >> (7 - (i & 7))) & 1)
+        });
+        self.position += bits;
+        Some(value)
+    }
+}
+
+mod reed_solomon {
+    //! Reed-Solomon error correction over GF(256), as used by QR codes.
+
+    const PRIMITIVE: u16 = 0x11d;
+
+    const fn tables() -> ([u8; 512], [u8; 256]) {
+        let mut exp = [0u8; 512];
+        let mut log = [0u8; 256];
+        let mut x: u16 = 1;
+        let mut i = 0;
+        while i < 255 {
+            exp[i] = x as u8;
+            exp[i + 255] = x as u8;
+            log[x as usize] = i as u8;
+            x <<= 1;
+            if x & 0x100 != 0 {
+                x ^= PRIMITIVE;
+            }
+            i += 1;
+        }
+        (exp, log)
+    }
+
+    const TABLES: ([u8; 512], [u8; 256]) = tables();
+    const EXP: [u8; 512] = TABLES.0;
+    const LOG: [u8; 256] = TABLES.1;
+
+    fn mul(a: u8, b: u8) -> u8 {
+        if a == 0 || b == 0 {
+            0
+        } else {
+            EXP[LOG[a as usize] as usize + LOG[b as usize] as usize]
+        }
+    }
+
+    fn div(a: u8, b: u8) -> u8 {
+        if a == 0 {
+            0
+        } else {
+            EXP[(LOG[a as usize] as usize + 255 - LOG[b as usize] as usize) % 255]
+        }
+    }
+
+    // α^e, for any e
+    fn alpha(e: usize) -> u8 {
+        EXP[e % 255]
+    }
+
+    // Evaluates a polynomial stored lowest degree first.
+    fn eval(poly: &[u8], x: u8) -> u8 {
+        poly.iter().rev().fold(0, |acc, &c| mul(acc, x) ^ c)
+    }
+
+    fn calculate_syndromes(block: &[u8], ecc_len: usize) -> Vec<u8> {
+        // the first codeword of the block is the highest degree coefficient
+        (0..ecc_len)
+            .map(|j| block.iter().fold(0, |acc, &c| mul(acc, alpha(j)) ^ c))
+            .collect()
+    }
+
+    /// Corrects `block`, data followed by `ecc_len` error correction codewords, in place.
+    /// Returns `None` if there are more errors than the code can correct.
+    pub(super) fn correct(block: &mut [u8], ecc_len: usize) -> Option<()> {
+        let syndromes = calculate_syndromes(block, ecc_len);
+        if syndromes.iter().all(|&s| s == 0) {
+            return Some(());
+        }
+
+        // Berlekamp-Massey, giving the error locator polynomial
+        let mut locator = vec![0u8; ecc_len + 1];
+        locator[0] = 1;
+        let mut previous = locator.clone();
+        let (mut errors, mut shift, mut last_discrepancy) = (0, 1, 1);
+        for k in 0..ecc_len {
+            let discrepancy = (1..=errors).fold(syndromes[k], |d, i| d ^ mul(locator[i], syndromes[k - i]));
+            if discrepancy == 0 {
+                shift += 1;
+                continue;
+            }
+
+            let scale = div(discrepancy, last_discrepancy);
+            let current = locator.clone();
+            for i in 0..=ecc_len - shift {
+                locator[i + shift] ^= mul(scale, previous[i]);
+            }
+            if 2 * errors <= k {
+                errors = k + 1 - errors;
+                previous = current;
+                last_discrepancy = discrepancy;
+                shift = 1;
+            } else {
+                shift += 1;
+            }
+        }
+        if 2 * errors > ecc_len {
+            return None;
+        }
+
+        // Chien search: an error on the coefficient of degree p is a root at α^-p
+        let n = block.len();
+        let positions: Vec<usize> = (0..n)
+            .filter(|&p| eval(&locator, alpha(255 - p % 255)) == 0)
+            .collect();
+        if positions.len() != errors {
+            return None;
+        }
+
+        // Forney: the error value at X is X·Ω(X⁻¹) / Λ'(X⁻¹), with Ω = S·Λ mod x^ecc_len
+        let evaluator: Vec<u8> = (0..ecc_len)
+            .map(|i| (0..=i).fold(0, |acc, j| acc ^ mul(syndromes[j], locator[i - j])))
+            .collect();
+        for p in positions {
+            let x_inv = alpha(255 - p % 255);
+            let derivative = (1..locator.len())
+                .step_by(2)
+                .fold(0, |acc, i| acc ^ mul(locator[i], alpha((255 - p % 255) * (i - 1))));
+            if derivative == 0 {
+                return None;
+            }
+            let magnitude = mul(alpha(p), eval(&evaluator, x_inv));
+            block[n - 1 - p] ^= div(magnitude, derivative);
+        }
+
+        calculate_syndromes(block, ecc_len)
+            .iter()
+            .all(|&s| s == 0)
+            .then_some(())
+    }
+}
+
+// Error correction codewords per block and number of blocks, by level (L, M, Q, H) and version.
+const ECC_CODEWORDS_PER_BLOCK: [[usize; 41]; 4] = [
+    [0, 7, 10, 15, 20, 26, 18, 20, 24, 30, 18, 20, 24, 26, 30, 22, 24, 28, 30, 28, 28, 28, 28, 30, 30, 26, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+    [0, 10, 16, 26, 18, 24, 16, 18, 22, 22, 26, 30, 22, 22, 24, 24, 28, 28, 26, 26, 26, 26, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28],
+    [0, 13, 22, 18, 26, 18, 24, 18, 22, 20, 24, 28, 26, 24, 20, 30, 24, 28, 28, 26, 30, 28, 30, 30, 30, 30, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+    [0, 17, 28, 22, 16, 22, 28, 26, 26, 24, 28, 24, 28, 22, 24, 24, 30, 28, 28, 26, 28, 30, 24, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+];
+
+const NUM_ERROR_CORRECTION_BLOCKS: [[usize; 41]; 4] = [
+    [0, 1, 1, 1, 1, 1, 2, 2, 2, 2, 4, 4, 4, 4, 4, 6, 6, 6, 6, 7, 8, 8, 9, 9, 10, 12, 12, 12, 13, 14, 15, 16, 17, 18, 19, 19, 20, 21, 22, 24, 25],
+    [0, 1, 1, 1, 2, 2, 4, 4, 4, 5, 5, 5, 8, 9, 9, 10, 10, 11, 13, 14, 16, 17, 17, 18, 20, 21, 23, 25, 26, 28, 29, 31, 33, 35, 37, 38, 40, 43, 45, 47, 49],
+    [0, 1, 1, 2, 2, 4, 4, 6, 6, 8, 8, 8, 10, 12, 16, 12, 17, 16, 18, 21, 20, 23, 23, 25, 27, 29, 34, 34, 35, 38, 40, 43, 45, 48, 51, 53, 56, 59, 62, 65, 68],
+    [0, 1, 1, 2, 4, 4, 4, 5, 6, 8, 8, 11, 11, 16, 16, 18, 16, 19, 21, 25, 25, 25, 34, 30, 32, 35, 37, 40, 42, 45, 48, 51, 54, 57, 60, 63, 66, 70, 74, 77, 81],
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use qrcode::{Color, EcLevel, QrCode, Version};
+
+    // Renders a code the way invoicing software does, with a quiet zone of 4 modules.
+    fn render(code: &QrCode, scale: usize) -> GrayImage {
+        let colors = code.to_colors();
+        let modules = code.width();
+        let size = (modules + 8) * scale;
+        let mut pixels = vec![255u8; size * size];
+        for (i, color) in colors.iter().enumerate() {
+            if *color == Color::Dark {
+                let (col, row) = (i % modules + 4, i / modules + 4);
+                for y in row * scale..(row + 1) * scale {
+                    for x in col * scale..(col + 1) * scale {
+                        pixels[y * size + x] = 0;
+                    }
+                }
+            }
+        }
+        GrayImage { width: size, height: size, pixels }
+    }
+
+    #[test]
+    fn decodes_every_version_and_level() {
+        let payload = "A:501234560*B:123456789*C:PT*D:FT*E:N*F:20241207*G:FT 2024/001*H:ABC12345-0001";
+        for version in 1..=40 {
+            for level in [EcLevel::L, EcLevel::M, EcLevel::Q, EcLevel::H] {
+                let Ok(code) = QrCode::with_version(payload, Version::Normal(version), level) else {
+                    continue;
+                };
+                let decoded = decode(&render(&code, 2));
+                assert_eq!(decoded.as_deref(), Some(payload), "version {} {:?}", version, level);
+            }
+        }
+    }
+
+    #[test]
+    fn corrects_damaged_modules() {
+        let payload = "https://example.com/fatura?id=ABC12345-0001&total=61.50";
+        let code = QrCode::with_error_correction_level(payload, EcLevel::M).unwrap();
+        let mut image = render(&code, 3);
+
+        // smudge a few modules in the data area
+        for (col, row) in [(12, 14), (15, 20), (20, 12)] {
+            let (x, y) = ((col + 4) * 3 + 1, (row + 4) * 3 + 1);
+            for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1), (2, 2), (2, 0), (0, 2), (2, 1), (1, 2)] {
+                let i = (y + dy - 1) * image.width + x + dx - 1;
+                image.pixels[i] = 255 - image.pixels[i];
+            }
+        }
+
+        assert_eq!(decode(&image).as_deref(), Some(payload));
+    }
+
+    #[test]
+    fn ignores_images_without_a_code() {
+        let blank = GrayImage { width: 10, height: 10, pixels: vec![255; 100] };
+        assert_eq!(decode(&blank), None);
+
+        // a diagonal line, light in the top-left corner of its bounding box
+        let mut line = blank.clone();
+        for i in 0..10 {
+            line.pixels[i * 10 + 9 - i] = 0;
+        }
+        assert_eq!(decode(&line), None);
+
+        // a round logo, and a solid square
+        let size = 40;
+        let disc = (0..size * size)
+            .map(|i| {
+                let (x, y) = ((i % size) as i64 - 20, (i / size) as i64 - 20);
+                if x * x + y * y < 300 { 0 } else { 255 }
+            })
+            .collect();
+        assert_eq!(decode(&GrayImage { width: size, height: size, pixels: disc }), None);
+        let square = GrayImage { width: size, height: size, pixels: vec![0; size * size] };
+        let mut framed = square.clone();
+        framed.pixels[0] = 255;
+        assert_eq!(decode(&square), None);
+        assert_eq!(decode(&framed), None);
+    }
+}
@@ -0,0 +1,426 @@
+// prover/lib/src/signature.rs
+//! Digital signatures of PDF bills, as PAdES makes them: a CMS SignedData in the `/Contents` of a
+//! signature dictionary, over the bytes of the file its `/ByteRange` selects.
+use crate::{Country, Date, Registry, ResidencyError};
+use cms::cert::CertificateChoices;
+use cms::content_info::ContentInfo;
+use cms::signed_data::{SignedData, SignerIdentifier};
+use const_oid::db::{rfc4519, rfc5911, rfc5912};
+use const_oid::ObjectIdentifier;
+use lopdf::{Document, Object};
+use p256::ecdsa::signature::Verifier;
+use rsa::pkcs1::DecodeRsaPublicKey;
+use rsa::{Pkcs1v15Sign, RsaPublicKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use x509_cert::der::asn1::OctetString;
+use x509_cert::der::{Decode, Encode, SliceReader, Tag, Tagged};
+use x509_cert::ext::pkix::{BasicConstraints, SubjectKeyIdentifier};
+use x509_cert::spki::SubjectPublicKeyInfoOwned;
+use x509_cert::time::Time;
+use x509_cert::Certificate;
+
+/// The most certificates followed from the signer up to a trusted root.
+const MAX_CHAIN_LENGTH: usize = 8;
+
+/// The organizationIdentifier attribute of a certificate subject, which eIDAS certificates of
+/// legal persons hold their tax number in, e.g. `VATPT-501234560`.
+const ORGANIZATION_IDENTIFIER: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.4.97");
+
+/// The certificate authorities that may vouch for the signing certificate of a bill, as
+/// DER-encoded root certificates.
+///
+/// The roots of each country are compiled into the program, see [`TrustedRoots::known`], so the
+/// prover cannot vouch for a bill with a certificate of its own. Roots are renewed every few
+/// years, proofs commit [`TrustedRoots::commitment`] so a verifier can tell which roots a proof
+/// was generated under.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrustedRoots {
+    certificates: BTreeSet<Vec<u8>>,
+}
+
+impl TrustedRoots {
+    pub fn new<I>(certificates: I) -> Self
+    where
+        I: IntoIterator<Item = Vec<u8>>,
+    {
+        Self {
+            certificates: certificates.into_iter().collect(),
+        }
+    }
+
+    /// The roots pinned by the validator of `country` in the default [`Registry`], e.g.
+    /// [`crate::portugal::ROOT_CERTIFICATES`].
+    pub fn known(country: Country) -> Self {
+        let registry = Registry::default();
+        let known = registry.get(country).map_or(&[][..], |v| v.root_certificates());
+        Self::new(known.iter().map(|der| der.to_vec()))
+    }
+
+    /// The hex-encoded SHA-256 of the sorted, hex-encoded SHA-256 fingerprints of the root
+    /// certificates, each followed by a newline.
+    pub fn commitment(&self) -> String {
+        let fingerprints: BTreeSet<String> = self
+            .certificates
+            .iter()
+            .map(|der| hex::encode(Sha256::digest(der)))
+            .collect();
+        let mut hasher = Sha256::new();
+        for fingerprint in fingerprints {
+            hasher.update(fingerprint.as_bytes());
+            hasher.update(b"\n");
+        }
+        hex::encode(hasher.finalize())
+    }
+
+    // Roots that cannot be parsed are left out, no certificate can chain to them
+    fn certificates(&self) -> Vec<Certificate> {
+        self.certificates
+            .iter()
+            .filter_map(|der| Certificate::from_der(der).ok())
+            .collect()
+    }
+}
+
+/// Checks that the document carries a digital signature over all of its bytes, made with a
+/// certificate that was valid on `date`, chains up to one of `roots`, and was issued to
+/// `organization`.
+///
+/// Roots also vouch for the certificates of people and other companies, so the certificate must
+/// name the issuer of the bill, as the organizationIdentifier (or serialNumber) of its subject,
+/// e.g. `VATPT-501234560`. Otherwise anyone with a certificate could sign a bill of their own.
+///
+/// Only SHA-256 digests are accepted, signed with RSA (PKCS#1 v1.5) or ECDSA on P-256.
+pub(crate) fn verify(
+    pdf_bytes: &[u8],
+    roots: &TrustedRoots,
+    date: Date,
+    organization: &str,
+) -> Result<(), ResidencyError> {
+    let (byte_range, contents) = signature_field(pdf_bytes)?;
+    let signed = signed_bytes(pdf_bytes, byte_range)?;
+    let signed_data = signed_data(&contents)?;
+
+    let certificates: Vec<Certificate> = signed_data
+        .certificates
+        .iter()
+        .flat_map(|set| set.0.iter())
+        .filter_map(|choice| match choice {
+            CertificateChoices::Certificate(certificate) => Some(certificate.clone()),
+            CertificateChoices::Other(_) => None,
+        })
+        .collect();
+
+    let [signer_info] = signed_data.signer_infos.0.as_slice() else {
+        return Err(invalid("expected a single signer"));
+    };
+    if signer_info.digest_alg.oid != rfc5912::ID_SHA_256 {
+        return Err(invalid("unsupported digest algorithm"));
+    }
+    let signer = certificates
+        .iter()
+        .find(|certificate| identifies(&signer_info.sid, certificate))
+        .ok_or_else(|| invalid("the signing certificate is missing"))?;
+
+    // with signed attributes, the signature is over them and they hold the digest of the document
+    let message = match &signer_info.signed_attrs {
+        Some(attributes) => {
+            let message_digest = attributes
+                .iter()
+                .find(|attribute| attribute.oid == rfc5911::ID_MESSAGE_DIGEST)
+                .and_then(|attribute| attribute.values.get(0))
+                .and_then(|value| value.decode_as::<OctetString>().ok())
+                .ok_or_else(|| invalid("the message digest is missing"))?;
+            if message_digest.as_bytes() != &Sha256::digest(&signed)[..] {
+                return Err(invalid("the document was changed after it was signed"));
+            }
+            attributes.to_der().map_err(invalid)?
+        }
+        None => signed,
+    };
+
+    let public_key = &signer.tbs_certificate.subject_public_key_info;
+    let algorithm = signer_info.signature_algorithm.oid;
+    if !verifies(public_key, algorithm, &message, signer_info.signature.as_bytes()) {
+        return Err(invalid("the signature does not match the signing certificate"));
+    }
+
+    chain(signer, &certificates, &roots.certificates(), date)?;
+    if !issued_to(signer, organization) {
+        return Err(ResidencyError::UntrustedSigner);
+    }
+    Ok(())
+}
+
+// Whether the subject of `certificate` identifies `organization`
+fn issued_to(certificate: &Certificate, organization: &str) -> bool {
+    certificate
+        .tbs_certificate
+        .subject
+        .0
+        .iter()
+        .flat_map(|rdn| rdn.0.iter())
+        .filter(|attribute| {
+            attribute.oid == ORGANIZATION_IDENTIFIER || attribute.oid == rfc4519::SERIAL_NUMBER
+        })
+        .filter(|attribute| matches!(attribute.value.tag(), Tag::Utf8String | Tag::PrintableString))
+        .any(|attribute| attribute.value.value() == organization.as_bytes())
+}
+
+// The byte range and CMS contents of the signature dictionary that covers the end of the file,
+// which is the last signature applied to it
+fn signature_field(pdf_bytes: &[u8]) -> Result<([usize; 4], Vec<u8>), ResidencyError> {
+    let document =
+        Document::load_mem(pdf_bytes).map_err(|e| ResidencyError::PdfParse(e.to_string()))?;
+
+    let fields: Vec<([usize; 4], Vec<u8>)> = document
+        .objects
+        .values()
+        .filter_map(|object| object.as_dict().ok())
+        .filter_map(|dict| {
+            let byte_range = dict
+                .get(b"ByteRange")
+                .and_then(Object::as_array)
+                .ok()?
+                .iter()
+                .map(|n| usize::try_from(n.as_i64().ok()?).ok())
+                .collect::<Option<Vec<_>>>()?;
+            let contents = dict.get(b"Contents").and_then(Object::as_str).ok()?;
+            Some((byte_range.try_into().ok()?, contents.to_vec()))
+        })
+        .collect();
+
+    if fields.is_empty() {
+        return Err(ResidencyError::NoSignature);
+    }
+    fields
+        .into_iter()
+        .find(|([_, _, start, length], _)| start.checked_add(*length) == Some(pdf_bytes.len()))
+        .ok_or_else(|| invalid("the document was changed after it was signed"))
+}
+
+// The bytes of the file outside the gap the signature is stored in, checking that the gap holds
+// nothing else
+fn signed_bytes(pdf_bytes: &[u8], byte_range: [usize; 4]) -> Result<Vec<u8>, ResidencyError> {
+    let [first_start, first_length, second_start, _] = byte_range;
+    if first_start != 0 || first_length > second_start || second_start > pdf_bytes.len() {
+        return Err(invalid("the byte range does not cover the document"));
+    }
+
+    let gap = &pdf_bytes[first_length..second_start];
+    let hex_string = gap.len() >= 2
+        && gap[0] == b'<'
+        && gap[gap.len() - 1] == b'>'
+        && gap[1..gap.len() - 1].iter().all(u8::is_ascii_hexdigit);
+    if !hex_string {
+        return Err(invalid("the byte range leaves out more than the signature"));
+    }
+
+    Ok([&pdf_bytes[..first_length], &pdf_bytes[second_start..]].concat())
+}
+
+fn signed_data(contents: &[u8]) -> Result<SignedData, ResidencyError> {
+    // the DER is followed by the zeros that pad the space reserved for it
+    let mut reader = SliceReader::new(contents).map_err(invalid)?;
+    let content_info = ContentInfo::decode(&mut reader).map_err(invalid)?;
+    if content_info.content_type != rfc5911::ID_SIGNED_DATA {
+        return Err(invalid("not a CMS SignedData"));
+    }
+
+    let signed_data = content_info.content.decode_as::<SignedData>().map_err(invalid)?;
+    if signed_data.encap_content_info.econtent.is_some() {
+        return Err(invalid("the signature is not detached from the document"));
+    }
+    Ok(signed_data)
+}
+
+// Follows the issuers of `certificate`, among the certificates of the signature and the roots,
+// until it reaches a root
+fn chain<'a>(
+    mut certificate: &'a Certificate,
+    intermediates: &'a [Certificate],
+    roots: &'a [Certificate],
+    date: Date,
+) -> Result<(), ResidencyError> {
+    for _ in 0..MAX_CHAIN_LENGTH {
+        if !valid_on(certificate, date) {
+            return Err(invalid("a certificate was not valid on the issue date"));
+        }
+        if roots.contains(certificate) {
+            return Ok(());
+        }
+        certificate = roots
+            .iter()
+            .chain(intermediates)
+            .find(|issuer| issued_by(certificate, issuer))
+            .ok_or(ResidencyError::UntrustedSigner)?;
+    }
+    Err(ResidencyError::UntrustedSigner)
+}
+
+fn identifies(sid: &SignerIdentifier, certificate: &Certificate) -> bool {
+    let tbs = &certificate.tbs_certificate;
+    match sid {
+        SignerIdentifier::IssuerAndSerialNumber(id) => {
+            id.issuer == tbs.issuer && id.serial_number == tbs.serial_number
+        }
+        SignerIdentifier::SubjectKeyIdentifier(key_id) => tbs
+            .get::<SubjectKeyIdentifier>()
+            .ok()
+            .flatten()
+            .is_some_and(|(_, subject_key_id)| subject_key_id == *key_id),
+    }
+}
+
+fn issued_by(certificate: &Certificate, issuer: &Certificate) -> bool {
+    let is_ca = issuer
+        .tbs_certificate
+        .get::<BasicConstraints>()
+        .ok()
+        .flatten()
+        .is_some_and(|(_, constraints)| constraints.ca);
+    let Ok(tbs) = certificate.tbs_certificate.to_der() else {
+        return false;
+    };
+
+    is_ca
+        && certificate.tbs_certificate.issuer == issuer.tbs_certificate.subject
+        && verifies(
+            &issuer.tbs_certificate.subject_public_key_info,
+            certificate.signature_algorithm.oid,
+            &tbs,
+            certificate.signature.raw_bytes(),
+        )
+}
+
+fn valid_on(certificate: &Certificate, date: Date) -> bool {
+    let day = |time: Time| Date::from_unix_timestamp(time.to_unix_duration().as_secs());
+    let validity = &certificate.tbs_certificate.validity;
+    day(validity.not_before) <= date && date <= day(validity.not_after)
+}
+
+// Whether `signature` is a SHA-256 signature of `message` by `public_key`
+fn verifies(
+    public_key: &SubjectPublicKeyInfoOwned,
+    algorithm: ObjectIdentifier,
+    message: &[u8],
+    signature: &[u8],
+) -> bool {
+    let key_algorithm = public_key.algorithm.oid;
+    let key = public_key.subject_public_key.raw_bytes();
+
+    match algorithm {
+        rfc5912::RSA_ENCRYPTION | rfc5912::SHA_256_WITH_RSA_ENCRYPTION
+            if key_algorithm == rfc5912::RSA_ENCRYPTION =>
+        {
+            RsaPublicKey::from_pkcs1_der(key).is_ok_and(|key| {
+                key.verify(Pkcs1v15Sign::new::<Sha256>(), &Sha256::digest(message), signature)
+                    .is_ok()
+            })
+        }
+        rfc5912::ECDSA_WITH_SHA_256 | rfc5912::ID_EC_PUBLIC_KEY
+            if key_algorithm == rfc5912::ID_EC_PUBLIC_KEY =>
+        {
+            let curve = public_key
+                .algorithm
+                .parameters
+                .as_ref()
+                .and_then(|parameters| parameters.decode_as::<ObjectIdentifier>().ok());
+            let (Ok(key), Ok(signature)) = (
+                p256::ecdsa::VerifyingKey::from_sec1_bytes(key),
+                p256::ecdsa::Signature::from_der(signature),
+            ) else {
+                return false;
+            };
+            curve == Some(rfc5912::SECP_256_R_1) && key.verify(message, &signature).is_ok()
+        }
+        _ => false,
+    }
+}
+
+fn invalid(reason: impl ToString) -> ResidencyError {
+    ResidencyError::InvalidSignature(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roots(file: &str) -> TrustedRoots {
+        TrustedRoots::new([std::fs::read(format!("../../examples/{}", file)).unwrap()])
+    }
+
+    const ISSUER: &str = "VATPT-501234560";
+
+    #[test]
+    fn verifies_rsa_and_ecdsa_signatures() {
+        let issued = Date::new(2024, 12, 7).unwrap();
+        for file in ["sample_invoice_signed.pdf", "sample_invoice_signed_ec.pdf"] {
+            let pdf_bytes = std::fs::read(format!("../../examples/{}", file)).unwrap();
+            assert_eq!(verify(&pdf_bytes, &roots("test_root_ca.der"), issued, ISSUER), Ok(()), "{}", file);
+            assert_eq!(
+                verify(&pdf_bytes, &roots("other_root_ca.der"), issued, ISSUER),
+                Err(ResidencyError::UntrustedSigner)
+            );
+            // the test certificates are valid from 2024 on
+            assert!(matches!(
+                verify(&pdf_bytes, &roots("test_root_ca.der"), Date::new(2023, 6, 1).unwrap(), ISSUER),
+                Err(ResidencyError::InvalidSignature(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn requires_certificate_of_the_issuer() {
+        let issued = Date::new(2024, 12, 7).unwrap();
+        let roots = roots("test_root_ca.der");
+
+        // the RSA certificate names the issuer as organizationIdentifier, the ECDSA one as
+        // serialNumber
+        let pdf_bytes = std::fs::read("../../examples/sample_invoice_signed.pdf").unwrap();
+        assert_eq!(
+            verify(&pdf_bytes, &roots, issued, "VATPT-503504564"),
+            Err(ResidencyError::UntrustedSigner)
+        );
+
+        // a valid signature, by a citizen whose certificate chains to the same root
+        let other = std::fs::read("../../examples/sample_invoice_signed_other.pdf").unwrap();
+        assert_eq!(verify(&other, &roots, issued, ISSUER), Err(ResidencyError::UntrustedSigner));
+        assert_eq!(verify(&other, &roots, issued, "IDCPT-12345678"), Ok(()));
+    }
+
+    #[test]
+    fn rejects_changed_or_unsigned_documents() {
+        let issued = Date::new(2024, 12, 7).unwrap();
+        let roots = roots("test_root_ca.der");
+        let pdf_bytes = std::fs::read("../../examples/sample_invoice_signed.pdf").unwrap();
+
+        // the signing time of the signature dictionary is signed too
+        let signing_time = pdf_bytes.windows(10).position(|w| w == b"D:20241207").unwrap();
+        let mut changed = pdf_bytes.clone();
+        changed[signing_time + 9] = b'8';
+        assert!(matches!(verify(&changed, &roots, issued, ISSUER), Err(ResidencyError::InvalidSignature(_))));
+
+        let appended = [pdf_bytes.as_slice(), b"\n% appended after signing\n"].concat();
+        assert!(matches!(verify(&appended, &roots, issued, ISSUER), Err(ResidencyError::InvalidSignature(_))));
+
+        let unsigned = std::fs::read("../../examples/sample_invoice.pdf").unwrap();
+        assert_eq!(verify(&unsigned, &roots, issued, ISSUER), Err(ResidencyError::NoSignature));
+    }
+
+    #[test]
+    fn commitment_ignores_order_and_duplicates() {
+        let a = TrustedRoots::new([vec![1, 2], vec![3]]);
+        let b = TrustedRoots::new([vec![3], vec![1, 2], vec![3]]);
+        assert_eq!(a.commitment(), b.commitment());
+        assert_ne!(a.commitment(), TrustedRoots::new([vec![3]]).commitment());
+    }
+
+    #[test]
+    fn known_roots_of_a_country() {
+        let pinned = crate::portugal::ROOT_CERTIFICATES.iter().map(|der| der.to_vec());
+        assert_eq!(TrustedRoots::known(Country::Portugal), TrustedRoots::new(pinned));
+        assert_eq!(TrustedRoots::known(Country::Spain), TrustedRoots::default());
+    }
+}
@@ -0,0 +1,216 @@
+// prover/lib/src/spain.rs
+use crate::{CountryValidator, Date, ExtractionHints, ResidencyError, Scope};
+use regex::Regex;
+use std::collections::BTreeMap;
+
+mod provinces;
+
+pub use provinces::province;
+
+/// Validates bills registered through VeriFactu or TicketBAI, the invoicing systems of the
+/// Spanish and Basque tax authorities.
+pub struct Spain;
+
+impl CountryValidator for Spain {
+    fn extraction_hints(&self) -> ExtractionHints {
+        ExtractionHints {
+            markers: &["VERI*FACTU", "VERIFACTU", "ValidarQR", "TicketBAI", "TBAI-"],
+        }
+    }
+
+    fn document_id(&self, text: &str) -> Option<String> {
+        find_ticketbai_id(text).or_else(|| find_verifactu_id(text))
+    }
+
+    fn issuer_tax_id(&self, text: &str, _qr_fields: &BTreeMap<String, String>) -> Option<String> {
+        // both identifiers start with the issuer NIF, whose control character they already checked
+        let id = self.document_id(text)?;
+        let id = id.strip_prefix("TBAI-").unwrap_or(&id);
+        Some(id[..9].to_string())
+    }
+
+    fn issue_date(&self, text: &str, _qr_fields: &BTreeMap<String, String>) -> Option<Date> {
+        // TicketBAI identifiers carry the date as DDMMYY, VeriFactu ones as DD-MM-YYYY
+        let id = self.document_id(text)?;
+        let (day, month, year) = match id.strip_prefix("TBAI-") {
+            Some(id) => (&id[10..12], &id[12..14], format!("20{}", &id[14..16])),
+            None => {
+                let date = id.rsplit(' ').next()?;
+                (&date[..2], &date[3..5], date[6..].to_string())
+            }
+        };
+        Date::new(year.parse().ok()?, month.parse().ok()?, day.parse().ok()?)
+    }
+
+    fn customer_labels(&self) -> &'static [&'static str] {
+        &["Cliente", "Titular", "Nombre", "NIF del cliente", "Destinatario"]
+    }
+
+    fn postal_code(&self, text: &str) -> Option<String> {
+        extract_postal_code(text)
+    }
+
+    fn postal_prefix(&self, postal_code: &str) -> String {
+        // the two digits of the province
+        postal_code.chars().take(2).collect()
+    }
+
+    fn resolve_scope(&self, postal_code: &str, scope: Scope) -> Result<String, ResidencyError> {
+        match scope {
+            Scope::Country => Ok("Spain".to_string()),
+            Scope::Region => province(postal_code)
+                .map(str::to_string)
+                .ok_or(ResidencyError::UnknownPostalCode),
+            Scope::City => Err(ResidencyError::UnsupportedScope(scope)),
+        }
+    }
+}
+
+pub(super) fn find_ticketbai_id(text: &str) -> Option<String> {
+    // TicketBAI identifier: TBAI-NNNNNNNNN-DDMMYY-XXXXXXXXXXXXX-CCC
+    // where N is the issuer NIF and C a CRC-8 of everything before it
+    let re = Regex::new(r"TBAI-([0-9A-Z]{9})-\d{6}-[0-9A-Za-z]{13}-(\d{3})").unwrap();
+
+    let valid_id = re
+        .captures_iter(text)
+        .find(|caps| {
+            let id = caps.get(0).unwrap().as_str();
+            is_valid_tax_id(&caps[1]) && format!("{:03}", crc8(&id.as_bytes()[..36])) == caps[2]
+        })
+        .map(|caps| caps[0].to_string());
+
+    valid_id
+}
+
+pub(super) fn find_verifactu_id(text: &str) -> Option<String> {
+    // The VeriFactu QR code links to the AEAT validation service with the issuer NIF, the
+    // invoice number and the issue date, which together identify the invoice
+    let re = Regex::new(r"ValidarQR\?nif=([0-9A-Z]{9})&numserie=([^&\s]+)&fecha=(\d{2}-\d{2}-\d{4})")
+        .unwrap();
+
+    let valid_id = re
+        .captures_iter(text)
+        .find(|caps| is_valid_tax_id(&caps[1]))
+        .map(|caps| format!("{} {} {}", &caps[1], &caps[2], &caps[3]));
+
+    valid_id
+}
+
+/// Labels of the address the service is supplied at. The issuer's own address, often printed
+/// first, is not the customer's.
+const SUPPLY_ADDRESS_LABELS: &[&str] = &[
+    "Dirección de suministro",
+    "Dirección del suministro",
+    "Punto de suministro",
+];
+
+/// Finds the postal code of the supply address, or the first one of the bill if it has no
+/// supply address label.
+pub(super) fn extract_postal_code(text: &str) -> Option<String> {
+    // Spanish postal code pattern: PPXXX followed by the town name,
+    // where PP is the province (01-52)
+    let postal_code_re = Regex::new(r"\b((?:0[1-9]|[1-4]\d|5[0-2])\d{3})\s+\p{Lu}").unwrap();
+    let label_re = Regex::new(&format!(
+        "(?i){}",
+        SUPPLY_ADDRESS_LABELS.iter().map(|label| regex::escape(label)).collect::<Vec<_>>().join("|")
+    ))
+    .unwrap();
+
+    let supply_address = label_re.find(text).map(|label| &text[label.end()..]);
+    supply_address
+        .and_then(|address| postal_code_re.captures(address))
+        .or_else(|| postal_code_re.captures(text))
+        .map(|caps| caps[1].to_string())
+}
+
+/// Checks the control character of a Spanish tax number: a NIF of a person (DNI), of a
+/// foreigner (NIE) or of a company (CIF).
+pub fn is_valid_tax_id(id: &str) -> bool {
+    let id = id.to_ascii_uppercase();
+    let bytes = id.as_bytes();
+    if bytes.len() != 9 || !bytes[1..8].iter().all(u8::is_ascii_digit) {
+        return false;
+    }
+
+    let (first, control) = (bytes[0], bytes[8]);
+    match first {
+        b'0'..=b'9' | b'X' | b'Y' | b'Z' => {
+            // a NIE stands for a DNI number starting with 0, 1 or 2
+            let first_digit = match first {
+                b'X' => 0,
+                b'Y' => 1,
+                b'Z' => 2,
+                digit => u32::from(digit - b'0'),
+            };
+            let number = bytes[1..8]
+                .iter()
+                .fold(first_digit, |acc, d| acc * 10 + u32::from(d - b'0'));
+
+            b"TRWAGMYFPDXBNJZSQVHLCKE"[(number % 23) as usize] == control
+        }
+        b'A'..=b'H' | b'J' | b'N' | b'P'..=b'S' | b'U'..=b'W' => {
+            let sum: u32 = bytes[1..8]
+                .iter()
+                .enumerate()
+                .map(|(i, d)| {
+                    let d = u32::from(d - b'0');
+                    if i % 2 == 0 { (d * 2) / 10 + (d * 2) % 10 } else { d }
+                })
+                .sum();
+            let digit = (10 - sum % 10) % 10;
+            let letter = b"JABCDEFGHI"[digit as usize];
+
+            match first {
+                // public bodies and foreign entities always use a letter
+                b'N' | b'P' | b'Q' | b'R' | b'S' | b'W' => control == letter,
+                // companies always use a digit
+                b'A' | b'B' | b'E' | b'H' => control == b'0' + digit as u8,
+                _ => control == letter || control == b'0' + digit as u8,
+            }
+        }
+        _ => false,
+    }
+}
+
+// CRC-8 as specified by TicketBAI: polynomial 0x07, no reflection, initial value 0
+fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |crc, &byte| {
+        (0..8).fold(crc ^ byte, |crc, _| {
+            if crc & 0x80 != 0 { (crc << 1) ^ 0x07 } else { crc << 1 }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_postal_code_of_supply_address() {
+        let text = "Energía Ejemplo S.A.\nPaseo de la Castellana, 1\n28046 Madrid\n\
+                    Dirección de suministro:\nCalle de Ejemplo, 12\n08013 Barcelona";
+        assert_eq!(extract_postal_code(text).as_deref(), Some("08013"));
+
+        // without a label the first postal code is taken
+        assert_eq!(extract_postal_code("Calle de Ejemplo, 12\n28013 Madrid").as_deref(), Some("28013"));
+    }
+
+    #[test]
+    fn validates_tax_ids() {
+        assert!(is_valid_tax_id("12345678Z"));
+        assert!(is_valid_tax_id("X1234567L"));
+        assert!(is_valid_tax_id("B12345674"));
+        assert!(!is_valid_tax_id("12345678A"));
+        assert!(!is_valid_tax_id("B12345675"));
+    }
+
+    #[test]
+    fn finds_ticketbai_id_with_valid_crc() {
+        let text = "Factura TBAI-B12345674-151124-Ab12Cd34Ef56G-244 Total";
+        assert_eq!(
+            find_ticketbai_id(text).as_deref(),
+            Some("TBAI-B12345674-151124-Ab12Cd34Ef56G-244")
+        );
+        assert_eq!(find_ticketbai_id("TBAI-B12345674-151124-Ab12Cd34Ef56G-245"), None);
+    }
+}
@@ -0,0 +1,63 @@
+// prover/lib/src/spain/provinces.rs
+
+/// Resolves the province of a Spanish postal code from its first two digits.
+pub fn province(postal_code: &str) -> Option<&'static str> {
+    let code: usize = postal_code.get(..2)?.parse().ok()?;
+    PROVINCES.get(code.checked_sub(1)?).copied()
+}
+
+// Provinces by INE code, which is also the prefix of their postal codes
+const PROVINCES: [&str; 52] = [
+    "Araba/Álava",
+    "Albacete",
+    "Alicante/Alacant",
+    "Almería",
+    "Ávila",
+    "Badajoz",
+    "Illes Balears",
+    "Barcelona",
+    "Burgos",
+    "Cáceres",
+    "Cádiz",
+    "Castellón/Castelló",
+    "Ciudad Real",
+    "Córdoba",
+    "A Coruña",
+    "Cuenca",
+    "Girona",
+    "Granada",
+    "Guadalajara",
+    "Gipuzkoa",
+    "Huelva",
+    "Huesca",
+    "Jaén",
+    "León",
+    "Lleida",
+    "La Rioja",
+    "Lugo",
+    "Madrid",
+    "Málaga",
+    "Murcia",
+    "Navarra",
+    "Ourense",
+    "Asturias",
+    "Palencia",
+    "Las Palmas",
+    "Pontevedra",
+    "Salamanca",
+    "Santa Cruz de Tenerife",
+    "Cantabria",
+    "Segovia",
+    "Sevilla",
+    "Soria",
+    "Tarragona",
+    "Teruel",
+    "Toledo",
+    "Valencia/València",
+    "Valladolid",
+    "Bizkaia",
+    "Zamora",
+    "Zaragoza",
+    "Ceuta",
+    "Melilla",
+];
@@ -0,0 +1,173 @@
+// prover/lib/src/text.rs
+//! Normalization of the text extracted from a bill, so that validators match the same patterns
+//! whatever software laid the bill out and whichever extractor read it.
+use unicode_normalization::UnicodeNormalization;
+
+/// Ordinal indicators, which NFKC would turn into plain letters: `3.º Esq.` must keep its meaning
+/// for address parsing.
+const KEPT_AS_IS: [char; 2] = ['º', 'ª'];
+
+/// Normalizes extracted text, deterministically, before validators run their patterns over it:
+///
+/// - Unicode NFKC, which splits ligatures (`ﬁ`), and maps full-width and other compatibility
+///   characters to their plain form, except for the ordinal indicators `º` and `ª`.
+/// - Every dash (hyphen, non-breaking hyphen, en dash, em dash, minus sign) becomes `-`, every
+///   space (no-break, thin, tab) a plain space, and invisible characters (zero-width spaces, soft
+///   hyphens, byte order marks) are dropped. Line and page breaks become `\n`.
+/// - Trailing spaces are trimmed and runs of empty lines reduced to one. Spaces within a line
+///   are kept, so that the columns of the layout stay apart and at their position.
+/// - Words hyphenated at the end of a line are joined back (`abasteci-` `mento`), codes broken at
+///   their dash too (`1000-` `001`), keeping it.
+/// - A label ending its line (with `:`) is reflowed onto the line of its value, if the value is
+///   printed under it rather than in a column to its left.
+pub fn normalize(text: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for line in unify_characters(&compatibility_decompose(text)).split('\n') {
+        let line = line.trim_end();
+        let Some(last) = lines.last_mut() else {
+            lines.push(line.to_string());
+            continue;
+        };
+        let rest = line.trim_start();
+        match continuation(last, line) {
+            Some(Join::Word) => {
+                last.pop();
+                last.push_str(rest);
+            }
+            Some(Join::Code) => last.push_str(rest),
+            Some(Join::Label) => {
+                last.push(' ');
+                last.push_str(rest);
+            }
+            None if line.is_empty() && last.is_empty() => {}
+            None => lines.push(line.to_string()),
+        }
+    }
+    lines.join("\n").trim_matches('\n').to_string()
+}
+
+/// How a line continues the one before it.
+enum Join {
+    /// The second half of a word hyphenated at the end of the line.
+    Word,
+    /// The rest of a code or number broken at its dash, e.g. a postal code or an ATCUD.
+    Code,
+    /// The value of a label.
+    Label,
+}
+
+fn continuation(previous: &str, line: &str) -> Option<Join> {
+    let indent = line.chars().take_while(|&c| c == ' ').count();
+    let next = line.trim_start().chars().next()?;
+    // the value of a label is printed under it, not in a column to its left
+    if previous.ends_with(':') && indent >= last_cell_column(previous) {
+        return Some(Join::Label);
+    }
+    let before_dash = previous.strip_suffix('-')?.chars().next_back()?;
+    if before_dash.is_lowercase() && next.is_lowercase() {
+        Some(Join::Word)
+    } else if before_dash.is_alphanumeric() && next.is_ascii_digit() {
+        Some(Join::Code)
+    } else {
+        None
+    }
+}
+
+// The column the last cell of a line starts at, cells being set apart by two spaces or more
+fn last_cell_column(line: &str) -> usize {
+    let start = line.rfind("  ").map_or(0, |i| i + 2);
+    line[..start].chars().count()
+}
+
+// NFKC everywhere but on the characters kept as they are
+fn compatibility_decompose(text: &str) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    for chunk in text.split_inclusive(KEPT_AS_IS) {
+        match chunk.strip_suffix(KEPT_AS_IS) {
+            Some(rest) => {
+                normalized.extend(rest.nfkc());
+                normalized.push_str(&chunk[rest.len()..]);
+            }
+            None => normalized.extend(chunk.nfkc()),
+        }
+    }
+    normalized
+}
+
+fn unify_characters(text: &str) -> String {
+    let text = text.replace("\r\n", "\n");
+    text.chars()
+        .filter_map(|c| match c {
+            '\u{2010}'..='\u{2015}' | '\u{2212}' | '\u{fe58}' | '\u{fe63}' | '\u{ff0d}' => Some('-'),
+            '\u{00ad}' | '\u{200b}'..='\u{200d}' | '\u{2060}' | '\u{feff}' => None,
+            '\r' | '\u{0b}' | '\u{0c}' | '\u{85}' | '\u{2028}' | '\u{2029}' => Some('\n'),
+            c if c.is_whitespace() && c != '\n' => Some(' '),
+            c => Some(c),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::portugal;
+
+    /// Text extracted from bills laid out by different invoicing software, with the ATCUD, the
+    /// issue date and the postal code of the supply address printed on each. The issuer's address
+    /// comes first on every one, and other codes shaped like an ATCUD before it on some.
+    const LAYOUTS: &[(&str, &str, &str, &str)] = &[
+        (include_str!("../../../examples/layouts/edp_two_columns.txt"), "JFR7K2QX-88213", "2024-11-28", "4050-123"),
+        (include_str!("../../../examples/layouts/galp_wrapped.txt"), "GLP24A9B-000123", "2024-10-15", "3000-456"),
+        (include_str!("../../../examples/layouts/epal_hyphenated.txt"), "EPAL2024-5567", "2024-09-30", "1170-089"),
+        (include_str!("../../../examples/layouts/meo_scanned_ocr.txt"), "MEO0T3L5-771", "2024-12-02", "2780-101"),
+        (include_str!("../../../examples/layouts/aguas_do_porto_labels.txt"), "ADP0W4T3-19842", "2024-08-19", "4200-072"),
+        (include_str!("../../../examples/layouts/nos_right_column.txt"), "NOS4F7K2-55120", "2024-11-02", "1200-095"),
+        (include_str!("../../../examples/layouts/vodafone_window.txt"), "VDF9Q2M4-39021", "2024-12-05", "3000-019"),
+    ];
+
+    #[test]
+    fn unifies_dashes_spaces_and_ligatures() {
+        assert_eq!(normalize("4050\u{2013}123\u{a0}Porto"), "4050-123 Porto");
+        assert_eq!(normalize("Eﬁciência energética\u{202f}\t"), "Eficiência energética");
+        assert_eq!(normalize("ＡＴＣＵＤ: ABC1\u{200b}2345\u{2011}0001"), "ATCUD: ABC12345-0001");
+        assert_eq!(normalize("Av. da Liberdade, n.º 110, 3.ª Esq."), "Av. da Liberdade, n.º 110, 3.ª Esq.");
+        assert_eq!(normalize("Sa\u{0303}o Joa\u{0303}o"), "São João");
+    }
+
+    #[test]
+    fn reflows_broken_lines() {
+        assert_eq!(normalize("consumo de abasteci-\nmento de água"), "consumo de abastecimento de água");
+        assert_eq!(normalize("Rua do Ouro 12\r\n1100-\r\n060 Lisboa"), "Rua do Ouro 12\n1100-060 Lisboa");
+        assert_eq!(normalize("ATCUD:\nABC12345-\n0001"), "ATCUD: ABC12345-0001");
+        assert_eq!(normalize("page 1\n\n\n\u{0c}\n\npage 2"), "page 1\n\npage 2");
+        assert_eq!(normalize("Lisboa      Data:\n            07/12/2024"), "Lisboa      Data: 07/12/2024");
+        // a line starting left of a label is not its value
+        assert_eq!(normalize("Lisboa      Data:\nRua do Ouro"), "Lisboa      Data:\nRua do Ouro");
+        // a dash between words, or before a capitalized line, is not a hyphenation
+        assert_eq!(normalize("Porto -\nPortugal"), "Porto -\nPortugal");
+        assert_eq!(normalize("Lisboa-\nTotal"), "Lisboa-\nTotal");
+    }
+
+    #[test]
+    fn extracts_fields_of_normalized_layouts() {
+        for (text, atcud, date, postal_code) in LAYOUTS {
+            // the raw text defeats at least one of the patterns
+            assert!(
+                portugal::is_valid_atcud(text).as_deref() != Some(atcud)
+                    || portugal::extract_issue_date(text).map(|d| d.to_string()).as_deref() != Some(date)
+                    || portugal::extract_postal_code(text).as_deref() != Some(postal_code)
+                    || portugal::extract_address(text).map(|address| address.postal_code).as_deref()
+                        != Some(postal_code),
+                "{atcud}"
+            );
+
+            let text = normalize(text);
+            assert_eq!(portugal::is_valid_atcud(&text).as_deref(), Some(*atcud));
+            assert_eq!(portugal::extract_issue_date(&text).map(|d| d.to_string()).as_deref(), Some(*date));
+            assert_eq!(portugal::extract_postal_code(&text).as_deref(), Some(*postal_code));
+            let address = portugal::extract_address(&text).map(|address| address.postal_code);
+            assert_eq!(address.as_deref(), Some(*postal_code));
+            assert_eq!(normalize(&text), text, "normalization is idempotent");
+        }
+    }
+}
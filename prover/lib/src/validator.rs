@@ -0,0 +1,125 @@
+// prover/lib/src/validator.rs
+use crate::{portugal::Portugal, spain::Spain, Address, Country, Date, HolderId, ResidencyError, Scope};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Hints that tell a correctly extracted bill apart from unrelated or garbled text.
+#[derive(Debug, Clone, Copy)]
+pub struct ExtractionHints {
+    /// Labels that every bill of the country prints, at least one of them must be found.
+    pub markers: &'static [&'static str],
+}
+
+/// What a known issuer bills households for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IssuerCategory {
+    Electricity,
+    Gas,
+    Water,
+    Telecom,
+}
+
+/// A company known to issue utility bills, e.g. an electricity or water supplier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KnownIssuer {
+    pub tax_id: &'static str,
+    pub name: &'static str,
+    pub category: IssuerCategory,
+}
+
+/// The country-specific rules `run` applies to the text of a bill.
+pub trait CountryValidator: Send + Sync {
+    fn extraction_hints(&self) -> ExtractionHints;
+
+    /// Finds the fiscal document identifier that makes the bill valid, e.g. the ATCUD in Portugal.
+    fn document_id(&self, text: &str) -> Option<String>;
+
+    /// Finds the tax number of the company that issued the bill, and checks its control digits.
+    /// `qr_fields` are the fields returned by [`CountryValidator::verify_qr_codes`].
+    fn issuer_tax_id(&self, text: &str, qr_fields: &BTreeMap<String, String>) -> Option<String>;
+
+    /// Finds the date the bill was issued on, preferring the fiscal QR code over the text.
+    fn issue_date(&self, text: &str, qr_fields: &BTreeMap<String, String>) -> Option<Date>;
+
+    /// Checks that the bill is addressed to `holder`. By default the holder has to be printed as
+    /// the value of one of [`CountryValidator::customer_labels`].
+    fn is_customer(&self, text: &str, _qr_fields: &BTreeMap<String, String>, holder: &HolderId) -> bool {
+        holder.appears_in(text, self.customer_labels())
+    }
+
+    /// The labels the customer's name or tax number is printed after on bills, e.g. `Cliente`.
+    /// Without any, no holder is found on the bill.
+    fn customer_labels(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// The utility companies trusted by [`crate::TrustedIssuers::known`].
+    fn known_issuers(&self) -> &'static [KnownIssuer] {
+        &[]
+    }
+
+    /// The DER-encoded root certificates trusted by [`crate::TrustedRoots::known`].
+    fn root_certificates(&self) -> &'static [&'static [u8]] {
+        &[]
+    }
+
+    /// Finds the postal code of the address the bill was issued to.
+    fn postal_code(&self, text: &str) -> Option<String>;
+
+    /// The start of `postal_code` that identifies the locality without the street, see
+    /// [`crate::Disclosure::postal_prefix`].
+    fn postal_prefix(&self, postal_code: &str) -> String;
+
+    /// Finds and normalizes the full address the bill was issued to. Countries without an
+    /// address parser cannot commit one.
+    fn address(&self, _text: &str) -> Option<Address> {
+        None
+    }
+
+    /// Resolves the location disclosed for `scope` from a postal code found by
+    /// [`CountryValidator::postal_code`].
+    fn resolve_scope(&self, postal_code: &str, scope: Scope) -> Result<String, ResidencyError>;
+
+    /// Checks `document_id` against the payloads of the QR codes found in the bill, and returns
+    /// the fields of the code that vouches for it.
+    ///
+    /// Countries without a mandatory fiscal QR code accept every bill.
+    fn verify_qr_codes(
+        &self,
+        _document_id: &str,
+        _qr_codes: &[String],
+    ) -> Result<BTreeMap<String, String>, ResidencyError> {
+        Ok(BTreeMap::new())
+    }
+}
+
+/// The validators `run` can pick from, keyed by [`Country`].
+pub struct Registry {
+    validators: HashMap<Country, Box<dyn CountryValidator>>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self {
+            validators: HashMap::new(),
+        }
+    }
+
+    pub fn register(&mut self, country: Country, validator: impl CountryValidator + 'static) {
+        self.validators.insert(country, Box::new(validator));
+    }
+
+    pub fn get(&self, country: Country) -> Option<&dyn CountryValidator> {
+        self.validators.get(&country).map(|v| v.as_ref())
+    }
+}
+
+impl Default for Registry {
+    /// A registry with every jurisdiction supported by this crate.
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry.register(Country::Portugal, Portugal);
+        registry.register(Country::Spain, Spain);
+        registry
+    }
+}
@@ -1,10 +1,13 @@
 // prover/program/src/main.rs
-//! A simple program that takes a regex pattern and a string and returns whether the string
-//! matches the pattern.
+//! Proves a residency action of a Hyle transaction: validates the bills, applies the action to
+//! the registry, and commits a `HyleOutput` whose `program_outputs` hold the JSON of the claim.
 #![no_main]
 
-use hyle_contract_sdk::{BlobIndex, HyleOutput, Identity, StateDigest, TxHash};
-use prover_lib::{Config, Country, Scope};
+use hyle_contract_sdk::{Blob, BlobIndex, HyleOutput, Identity, StateDigest, TxHash};
+use prover_lib::{Config, Disclosure, DkimKey, Freshness, Holder, TrustedIssuers};
+use prover_registry::{
+    Authorization, RegistryError, RegistryState, RegistryWitness, ResidencyAction,
+};
 sp1_zkvm::entrypoint!(main);
 
 // These two lines are necessary for the program to properly compile.
@@ -13,25 +16,94 @@ sp1_zkvm::entrypoint!(main);
 // inside the zkVM.
 
 pub fn main() {
-    // Read a series of bytes from the input, which should be a PDF file.
-    let pdf_bytes = sp1_zkvm::io::read::<Vec<u8>>();
+    // Read the bills from the input, as PDF files. Several bills from distinct issuers make a
+    // stronger claim, they are combined into one.
+    let documents = sp1_zkvm::io::read::<Vec<Vec<u8>>>();
     let tx_hash = sp1_zkvm::io::read::<String>();
+    // The blobs of the transaction, the one at `index` holds the residency action to prove.
+    let blobs = sp1_zkvm::io::read::<Vec<Blob>>();
+    let index = sp1_zkvm::io::read::<BlobIndex>();
+    // Bills are only accepted from the issuers compiled into the program for the country, the
+    // claim commits the hash of that list so verifiers know which policy the proof followed. The
+    // registry policy may require it regardless.
+    let known_issuers_only = sp1_zkvm::io::read::<bool>();
+    // The current date and maximum age of the bill, the program has no clock of its own.
+    let freshness = sp1_zkvm::io::read::<Option<Freshness>>();
+    // The claimant's name or NIF and a secret salt, only their salted hash is committed.
+    let holder = sp1_zkvm::io::read::<Option<Holder>>();
+    // A secret salt to commit the hash of the normalized address with, if any.
+    let address_salt = sp1_zkvm::io::read::<Option<[u8; 32]>>();
+    // The attributes to reveal, the claim commits the hash of this policy with them.
+    let disclosure = sp1_zkvm::io::read::<Option<Disclosure>>();
+    // How many months the bills must span at the same address, if any.
+    let min_residency_months = sp1_zkvm::io::read::<Option<u32>>();
+    // The public key the emails the bills were attached to must be DKIM-signed with, if any. The
+    // claim commits its hash and the signing domain.
+    let dkim_key = sp1_zkvm::io::read::<Option<DkimKey>>();
+    // The part of the registry the action touches, with its Merkle paths. Its root is the state
+    // the proof starts from, so it only settles if that is the contract state.
+    let mut registry = sp1_zkvm::io::read::<RegistryWitness>();
+    // Who revokes the registration, only used by revoke actions, which take no bill.
+    let authorization = sp1_zkvm::io::read::<Option<Authorization>>();
+    let initial_state = registry.root();
 
-    let result = prover_lib::run(&pdf_bytes, Config {
-        scope: Scope::Country,
-        country: Country::Portugal,
-    });
+    let result = blobs
+        .get(index.0 as usize)
+        .ok_or(RegistryError::MissingBlob)
+        .and_then(|blob| ResidencyAction::from_blob(&blob.contract_name.0, &blob.data.0))
+        .and_then(|action| {
+            if let ResidencyAction::Revoke { identity } = &action {
+                let authorization = authorization.ok_or(RegistryError::Unauthorized)?;
+                registry.revoke(identity, &authorization)?;
+                return Ok(None);
+            }
+            let (country, scope) = action.proven_scope().ok_or(RegistryError::ActionMismatch)?;
+
+            // The country picks the validator, the scope decides which location attribute is
+            // proven and committed.
+            let residency = prover_lib::run_all(&documents, Config {
+                scope,
+                country,
+                trusted_issuers: (known_issuers_only || registry.policy().known_issuers_only)
+                    .then(|| TrustedIssuers::known(country)),
+                freshness,
+                holder,
+                // the nullifiers are derived in the domain the registry pins, so each bill
+                // registers once
+                nullifier_domain: registry.policy().nullifier_domain.clone(),
+                validity_days: registry.policy().validity_days,
+                address_salt,
+                disclosure,
+                min_residency_months,
+                // no root certificate is pinned yet, see `prover_lib::portugal::ROOT_CERTIFICATES`,
+                // so requiring signed bills would reject all of them
+                trusted_roots: None,
+                dkim_key,
+            })?;
+
+            registry.apply(&action, &residency)?;
+            Ok(Some(residency.claim()))
+        });
+
+    // Commit the claim, or the reason the bill was rejected, so verifiers learn more than `success`.
+    // Revocations commit no claim.
+    let program_outputs = serde_json::to_vec(&result).unwrap();
 
-    // TODO: Improve the state on Hyle to be more meaningful and useful (using timestamps and scoped location)
     sp1_zkvm::io::commit(&HyleOutput {
-        program_outputs: vec![],
+        program_outputs,
         version: 1,
-        initial_state: StateDigest("".as_bytes().to_vec()),
-        next_state: StateDigest("Portugal".as_bytes().to_vec()), // TODO: change this to the actual next state
+        initial_state: StateDigest(initial_state.to_vec()),
+        // a rejected bill leaves the registry untouched
+        next_state: StateDigest(registry.root().to_vec()),
         identity: Identity::default(),
         tx_hash: TxHash(tx_hash),
-        index: BlobIndex(0),
-        blobs: vec![], // TODO: change this to the actual payloads
-        success: result,
+        index,
+        blobs: flatten_blobs(&blobs),
+        success: result.is_ok(),
     });
 }
+
+/// The blobs of the transaction as Hyle commits them, their data concatenated in order.
+fn flatten_blobs(blobs: &[Blob]) -> Vec<u8> {
+    blobs.iter().flat_map(|blob| blob.data.0.iter().copied()).collect()
+}
@@ -0,0 +1,95 @@
+// prover/registry/src/action.rs
+use crate::RegistryError;
+use prover_lib::{Country, Scope};
+use serde::{Deserialize, Serialize};
+
+/// The name the residency contract is registered under, the only contract whose blobs the
+/// program proves actions of.
+pub const CONTRACT_NAME: &str = "sp1_residency";
+
+/// What a transaction asks the residency contract to do, sent as the JSON data of its blob, e.g.
+/// `{"action":"register","country":"Portugal","scope":"City"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ResidencyAction {
+    /// Registers the holder as resident of the location proven for `scope`.
+    Register { country: Country, scope: Scope },
+    /// Extends the registration of the holder with a newer bill, moving it to the location the
+    /// bill proves for `scope`.
+    Renew { country: Country, scope: Scope },
+    /// Removes the registration of `identity`, see [`crate::Authorization`] for who may do so.
+    Revoke { identity: String },
+}
+
+impl ResidencyAction {
+    pub fn decode(data: &[u8]) -> Result<Self, RegistryError> {
+        serde_json::from_slice(data).map_err(|_| RegistryError::InvalidAction)
+    }
+
+    /// Decodes the action of a blob, which must be addressed to [`CONTRACT_NAME`].
+    pub fn from_blob(contract_name: &str, data: &[u8]) -> Result<Self, RegistryError> {
+        if contract_name != CONTRACT_NAME {
+            return Err(RegistryError::ActionMismatch);
+        }
+        Self::decode(data)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("actions always serialize")
+    }
+
+    /// The country and scope the bill of the action must be proven for, `None` if the action
+    /// takes no bill.
+    pub fn proven_scope(&self) -> Option<(Country, Scope)> {
+        match self {
+            ResidencyAction::Register { country, scope }
+            | ResidencyAction::Renew { country, scope } => Some((*country, *scope)),
+            ResidencyAction::Revoke { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_blob_data() {
+        let action = ResidencyAction::decode(br#"{"scope":"City","action":"register","country":"Portugal"}"#);
+        assert_eq!(
+            action,
+            Ok(ResidencyAction::Register {
+                country: Country::Portugal,
+                scope: Scope::City,
+            })
+        );
+        assert_eq!(
+            ResidencyAction::decode(&action.unwrap().encode()).unwrap().proven_scope(),
+            Some((Country::Portugal, Scope::City))
+        );
+        assert_eq!(
+            ResidencyAction::decode(br#"{"action":"renew","country":"Spain","scope":"Region"}"#),
+            Ok(ResidencyAction::Renew {
+                country: Country::Spain,
+                scope: Scope::Region,
+            })
+        );
+        assert_eq!(
+            ResidencyAction::decode(br#"{"action":"revoke","identity":"ab12"}"#)
+                .unwrap()
+                .proven_scope(),
+            None
+        );
+
+        // the blob sent before actions existed
+        assert_eq!(ResidencyAction::decode(b"Portugal"), Err(RegistryError::InvalidAction));
+
+        // blobs of other contracts are not for the program to prove
+        let register = br#"{"action":"register","country":"Portugal","scope":"City"}"#;
+        assert!(ResidencyAction::from_blob(CONTRACT_NAME, register).is_ok());
+        assert_eq!(
+            ResidencyAction::from_blob("other_contract", register),
+            Err(RegistryError::ActionMismatch)
+        );
+    }
+}
@@ -0,0 +1,726 @@
+// prover/registry/src/lib.rs
+//! The on-chain state of the residency contract: who is registered as resident where, and until
+//! when.
+//!
+//! Only the Merkle root of the registry is stored on chain, as the `HyleOutput` state digest. The
+//! prover keeps the registry itself, and hands the program a [`RegistryWitness`] of the entry,
+//! nullifiers and admin key a transaction touches, which it checks against the current root
+//! before committing the root of the updated registry.
+mod action;
+mod merkle;
+mod revocation;
+mod witness;
+
+pub use action::{ResidencyAction, CONTRACT_NAME};
+pub use merkle::{MembershipProof, MerkleProof};
+pub use revocation::{revocation_message, Authorization};
+pub use witness::RegistryWitness;
+
+use prover_lib::{
+    Country, Date, Residency, ResidencyError, Scope, TrustedIssuers, DEFAULT_VALIDITY_DAYS,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use thiserror::Error;
+
+/// A registered resident, one per identity.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    /// The [`prover_lib::Holder::commitment`] of the resident.
+    pub identity: String,
+    pub country: Country,
+    pub scope: Scope,
+    /// The location proven for `scope`, see [`prover_lib::Residency::location`].
+    pub location: String,
+    /// The last day the registration is valid, the [`prover_lib::Residency::expiry`] of the
+    /// latest bill.
+    pub expiry: Date,
+}
+
+impl Entry {
+    fn new(identity: String, residency: &Residency) -> Self {
+        Self {
+            identity,
+            country: residency.country,
+            scope: residency.scope,
+            location: residency.location.clone(),
+            expiry: residency.expiry,
+        }
+    }
+}
+
+/// The rules the bills of every registration must have been validated under. They are part of
+/// the registry state, so the prover cannot pick its own.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Policy {
+    /// The domain the nullifiers of the bills are derived in. A bill has a distinct nullifier in
+    /// every domain, so a domain chosen by the prover would let it register more than once.
+    pub nullifier_domain: String,
+    /// The validity window of the bills, see [`prover_lib::Config::validity_days`], so that a
+    /// registration lasts as long whoever proves it.
+    pub validity_days: u32,
+    /// Whether the bills must come from the issuers known for their country, see
+    /// [`prover_lib::TrustedIssuers::known`].
+    pub known_issuers_only: bool,
+    /// The oldest a bill may be, in days, if the bills must be checked for freshness. The program
+    /// has no clock, so the date their age is counted to is still the prover's, see
+    /// [`prover_lib::Freshness`].
+    pub max_age_days: Option<u32>,
+}
+
+impl Policy {
+    /// Checks that `residency` was validated under the policy.
+    fn check(&self, residency: &Residency) -> Result<(), RegistryError> {
+        let known_issuers = self
+            .known_issuers_only
+            .then(|| TrustedIssuers::known(residency.country).commitment());
+        let fresh = match (self.max_age_days, residency.freshness) {
+            (None, _) => true,
+            (Some(max_age_days), Some(freshness)) => freshness.max_age_days <= max_age_days,
+            (Some(_), None) => false,
+        };
+
+        if residency.nullifier_domain != self.nullifier_domain
+            || residency.validity_days != self.validity_days
+            || (known_issuers.is_some() && residency.trusted_issuers != known_issuers)
+            || !fresh
+        {
+            return Err(RegistryError::PolicyMismatch);
+        }
+        Ok(())
+    }
+
+    fn hash(&self) -> [u8; 32] {
+        merkle::leaf(&serde_json::to_vec(self).expect("policies always serialize"))
+    }
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            nullifier_domain: CONTRACT_NAME.to_string(),
+            validity_days: DEFAULT_VALIDITY_DAYS,
+            known_issuers_only: false,
+            max_age_days: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResidencyRegistry {
+    policy: Policy,
+    entries: BTreeMap<String, Entry>,
+    /// The nullifiers of every bill ever registered, so a bill cannot be used twice.
+    nullifiers: BTreeSet<String>,
+    /// The Ed25519 keys allowed to revoke any registration.
+    admins: BTreeSet<[u8; 32]>,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RegistryError {
+    #[error(transparent)]
+    Residency(#[from] ResidencyError),
+
+    #[error("The transaction has no blob at the proven index")]
+    MissingBlob,
+
+    #[error("The blob is not a valid residency action")]
+    InvalidAction,
+
+    #[error("The proven claim does not match the action of the transaction")]
+    ActionMismatch,
+
+    #[error("The claim is not bound to a holder identity")]
+    MissingHolder,
+
+    #[error("The bill was not validated under the policy of the registry")]
+    PolicyMismatch,
+
+    #[error("The registry witness does not match the registry state")]
+    InvalidWitness,
+
+    #[error("The bill was already used for a registration")]
+    NullifierUsed,
+
+    #[error("The holder is already registered")]
+    AlreadyRegistered,
+
+    #[error("The holder is not registered")]
+    NotRegistered,
+
+    #[error("The bill does not extend the registration")]
+    NoExtension,
+
+    #[error("Not authorized to revoke the registration")]
+    Unauthorized,
+}
+
+/// The registry as the rules of the contract see it: the whole [`ResidencyRegistry`] kept by the
+/// prover, or the [`RegistryWitness`] of the part a transaction touches, which the program gets.
+///
+/// Reading what the witness does not cover fails with [`RegistryError::InvalidWitness`], so a
+/// transition only succeeds on a witness if it does on the whole registry.
+pub trait RegistryState {
+    fn policy(&self) -> &Policy;
+
+    /// The state digest of the registry: the root of its entries, bound to the root of its
+    /// nullifiers, of its admins and to its policy.
+    fn root(&self) -> [u8; 32];
+
+    /// The registration of `identity`, if any.
+    fn entry(&self, identity: &str) -> Result<Option<Entry>, RegistryError>;
+
+    fn is_admin(&self, key: &[u8; 32]) -> Result<bool, RegistryError>;
+
+    /// Marks the nullifiers of the bills as used, or fails with [`RegistryError::NullifierUsed`]
+    /// and leaves them untouched if any of them was.
+    fn spend(&mut self, nullifiers: &[String]) -> Result<(), RegistryError>;
+
+    /// Replaces the registration of `identity`, or removes it if `entry` is `None`.
+    fn set_entry(&mut self, identity: &str, entry: Option<Entry>) -> Result<(), RegistryError>;
+
+    /// Applies the action of a transaction, given the bill validated for it.
+    fn apply(
+        &mut self,
+        action: &ResidencyAction,
+        residency: &Residency,
+    ) -> Result<Entry, RegistryError> {
+        if action.proven_scope() != Some((residency.country, residency.scope)) {
+            return Err(RegistryError::ActionMismatch);
+        }
+
+        match action {
+            ResidencyAction::Register { .. } => self.register(residency),
+            ResidencyAction::Renew { .. } => self.renew(residency),
+            ResidencyAction::Revoke { .. } => Err(RegistryError::ActionMismatch),
+        }
+    }
+
+    /// Registers the holder of a validated bill, valid until the expiry of its claim.
+    fn register(&mut self, residency: &Residency) -> Result<Entry, RegistryError> {
+        let identity = residency.holder.clone().ok_or(RegistryError::MissingHolder)?;
+        self.policy().check(residency)?;
+        if self.entry(&identity)?.is_some() {
+            return Err(RegistryError::AlreadyRegistered);
+        }
+
+        let entry = Entry::new(identity.clone(), residency);
+        self.spend(&residency.nullifiers)?;
+        self.set_entry(&identity, Some(entry.clone()))?;
+        Ok(entry)
+    }
+
+    /// Replaces the registration of the holder of a validated bill, which must expire later than
+    /// the bill the holder registered with.
+    fn renew(&mut self, residency: &Residency) -> Result<Entry, RegistryError> {
+        let identity = residency.holder.clone().ok_or(RegistryError::MissingHolder)?;
+        self.policy().check(residency)?;
+        let entry = self.entry(&identity)?.ok_or(RegistryError::NotRegistered)?;
+        if residency.expiry <= entry.expiry {
+            return Err(RegistryError::NoExtension);
+        }
+
+        let entry = Entry::new(identity.clone(), residency);
+        self.spend(&residency.nullifiers)?;
+        self.set_entry(&identity, Some(entry.clone()))?;
+        Ok(entry)
+    }
+
+    /// Removes the registration of `identity`. The nullifiers of its bills stay used, so the
+    /// bills cannot register anyone again.
+    fn revoke(
+        &mut self,
+        identity: &str,
+        authorization: &Authorization,
+    ) -> Result<Entry, RegistryError> {
+        let entry = self.entry(identity)?.ok_or(RegistryError::NotRegistered)?;
+        if !authorization.allows(identity, self)? {
+            return Err(RegistryError::Unauthorized);
+        }
+
+        self.set_entry(identity, None)?;
+        Ok(entry)
+    }
+}
+
+impl ResidencyRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An empty registry whose registrations can be revoked by the holders of `admins`.
+    pub fn with_admins(admins: impl IntoIterator<Item = [u8; 32]>) -> Self {
+        Self {
+            admins: admins.into_iter().collect(),
+            ..Self::default()
+        }
+    }
+
+    /// An empty registry whose bills must be validated under `policy`.
+    pub fn with_policy(policy: Policy, admins: impl IntoIterator<Item = [u8; 32]>) -> Self {
+        Self {
+            policy,
+            ..Self::with_admins(admins)
+        }
+    }
+
+    pub fn get(&self, identity: &str) -> Option<&Entry> {
+        self.entries.get(identity)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Proves that `identity` is registered to a verifier that only knows [`RegistryState::root`].
+    pub fn prove(&self, identity: &str) -> Option<MembershipProof> {
+        self.entries.get(identity)?;
+
+        Some(MembershipProof {
+            path: merkle::prove(&self.entry_leaves(), &merkle::key(identity.as_bytes())),
+            nullifiers_root: merkle::root(&self.nullifier_leaves()),
+            admins_root: merkle::root(&self.admin_leaves()),
+            policy_hash: self.policy.hash(),
+        })
+    }
+
+    /// The witness of the part of the registry a transaction touches: the registration of
+    /// `identity`, the nullifiers of its bills, and the key of the admin revoking, if any.
+    pub fn witness(
+        &self,
+        identity: &str,
+        nullifiers: &[String],
+        admin: Option<&[u8; 32]>,
+    ) -> RegistryWitness {
+        let admin_leaves = self.admin_leaves();
+        // each nullifier is proven unused in the tree the previous ones were added to
+        let mut nullifier_leaves = self.nullifier_leaves();
+        let mut nullifier_paths = Vec::with_capacity(nullifiers.len());
+        for nullifier in nullifiers {
+            let key = merkle::key(nullifier.as_bytes());
+            nullifier_paths.push(merkle::prove(&nullifier_leaves, &key));
+            if let Err(index) = nullifier_leaves.binary_search_by(|(k, _)| k.cmp(&key)) {
+                nullifier_leaves.insert(index, (key, merkle::leaf(nullifier.as_bytes())));
+            }
+        }
+
+        RegistryWitness {
+            policy: self.policy.clone(),
+            entries_root: merkle::root(&self.entry_leaves()),
+            nullifiers_root: merkle::root(&self.nullifier_leaves()),
+            admins_root: merkle::root(&admin_leaves),
+            entry: self.entries.get(identity).cloned(),
+            entry_path: merkle::prove(&self.entry_leaves(), &merkle::key(identity.as_bytes())),
+            nullifier_paths,
+            admin_path: admin.map(|key| merkle::prove(&admin_leaves, &merkle::key(key))),
+        }
+    }
+
+    fn entry_leaves(&self) -> Vec<([u8; 32], [u8; 32])> {
+        sorted(self.entries.values().map(|entry| {
+            (merkle::key(entry.identity.as_bytes()), merkle::entry_leaf(entry))
+        }))
+    }
+
+    fn nullifier_leaves(&self) -> Vec<([u8; 32], [u8; 32])> {
+        sorted(self.nullifiers.iter().map(|nullifier| {
+            (merkle::key(nullifier.as_bytes()), merkle::leaf(nullifier.as_bytes()))
+        }))
+    }
+
+    fn admin_leaves(&self) -> Vec<([u8; 32], [u8; 32])> {
+        sorted(self.admins.iter().map(|key| (merkle::key(key), merkle::leaf(key))))
+    }
+}
+
+impl RegistryState for ResidencyRegistry {
+    fn policy(&self) -> &Policy {
+        &self.policy
+    }
+
+    fn root(&self) -> [u8; 32] {
+        merkle::state_root(
+            &merkle::root(&self.entry_leaves()),
+            &merkle::root(&self.nullifier_leaves()),
+            &merkle::root(&self.admin_leaves()),
+            &self.policy.hash(),
+        )
+    }
+
+    fn entry(&self, identity: &str) -> Result<Option<Entry>, RegistryError> {
+        Ok(self.entries.get(identity).cloned())
+    }
+
+    fn is_admin(&self, key: &[u8; 32]) -> Result<bool, RegistryError> {
+        Ok(self.admins.contains(key))
+    }
+
+    fn spend(&mut self, nullifiers: &[String]) -> Result<(), RegistryError> {
+        if nullifiers.iter().any(|n| self.nullifiers.contains(n)) {
+            return Err(RegistryError::NullifierUsed);
+        }
+        self.nullifiers.extend(nullifiers.iter().cloned());
+        Ok(())
+    }
+
+    fn set_entry(&mut self, identity: &str, entry: Option<Entry>) -> Result<(), RegistryError> {
+        match entry {
+            Some(entry) => self.entries.insert(identity.to_string(), entry),
+            None => self.entries.remove(identity),
+        };
+        Ok(())
+    }
+}
+
+// The leaves of a tree, sorted by key as the tree orders them
+fn sorted(leaves: impl Iterator<Item = ([u8; 32], [u8; 32])>) -> Vec<([u8; 32], [u8; 32])> {
+    let mut leaves: Vec<_> = leaves.collect();
+    leaves.sort_unstable();
+    leaves
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+    use prover_lib::{Freshness, Holder, HolderId};
+    use std::collections::BTreeMap;
+
+    fn residency(holder: &str, nullifier: &str) -> Residency {
+        Residency {
+            document_id: "ABC12345-0001".to_string(),
+            issuer_tax_id: "501234560".to_string(),
+            issue_date: Date::new(2024, 12, 7).unwrap(),
+            first_issue_date: Date::new(2024, 12, 7).unwrap(),
+            expiry: Date::new(2025, 12, 7).unwrap(),
+            validity_days: DEFAULT_VALIDITY_DAYS,
+            postal_code: "4050-123".to_string(),
+            normalized_address: None,
+            country: Country::Portugal,
+            scope: Scope::City,
+            location: "Porto".to_string(),
+            disclosed: Default::default(),
+            disclosure: String::new(),
+            qr_fields: BTreeMap::new(),
+            trusted_issuers: None,
+            trusted_roots: None,
+            dkim_key: None,
+            dkim_domain: None,
+            dkim_selector: None,
+            freshness: None,
+            holder: Some(holder.to_string()),
+            address: None,
+            resident_since_at_least: None,
+            nullifier_domain: CONTRACT_NAME.to_string(),
+            nullifiers: vec![nullifier.to_string()],
+        }
+    }
+
+    #[test]
+    fn registration_changes_the_root() {
+        let mut registry = ResidencyRegistry::new();
+        let empty_root = registry.root();
+
+        let entry = registry.register(&residency("alice", "n1")).unwrap();
+        assert_eq!(entry.location, "Porto");
+        assert_eq!(entry.expiry, Date::new(2025, 12, 7).unwrap());
+        assert_ne!(registry.root(), empty_root);
+
+        // the root only depends on the contents, not on the order of registrations
+        let root = registry.root();
+        registry.register(&residency("bob", "n2")).unwrap();
+        let mut other = ResidencyRegistry::new();
+        other.register(&residency("bob", "n2")).unwrap();
+        other.register(&residency("alice", "n1")).unwrap();
+        assert_eq!(registry.root(), other.root());
+        assert_ne!(registry.root(), root);
+    }
+
+    #[test]
+    fn rejects_invalid_transitions() {
+        let mut registry = ResidencyRegistry::new();
+        registry.register(&residency("alice", "n1")).unwrap();
+        let root = registry.root();
+
+        assert_eq!(
+            registry.register(&residency("bob", "n1")),
+            Err(RegistryError::NullifierUsed)
+        );
+        assert_eq!(
+            registry.register(&residency("alice", "n2")),
+            Err(RegistryError::AlreadyRegistered)
+        );
+        // a combined claim is rejected if any of its bills was used
+        let mut combined = residency("bob", "n2");
+        combined.nullifiers.push("n1".to_string());
+        assert_eq!(registry.register(&combined), Err(RegistryError::NullifierUsed));
+        let mut anonymous = residency("carol", "n3");
+        anonymous.holder = None;
+        assert_eq!(registry.register(&anonymous), Err(RegistryError::MissingHolder));
+        // the same bill has another nullifier in another domain
+        let mut elsewhere = residency("carol", "n1-elsewhere");
+        elsewhere.nullifier_domain = "another-application".to_string();
+        assert_eq!(registry.register(&elsewhere), Err(RegistryError::PolicyMismatch));
+        // a longer window than the registry's would outlast the other registrations
+        let mut lasting = residency("carol", "n3");
+        lasting.validity_days = 730;
+        assert_eq!(registry.register(&lasting), Err(RegistryError::PolicyMismatch));
+
+        // bills checked against fewer issuers, or an older bill, than the registry asks for
+        let mut strict = ResidencyRegistry::with_policy(
+            Policy {
+                known_issuers_only: true,
+                max_age_days: Some(90),
+                ..Policy::default()
+            },
+            [],
+        );
+        let mut checked = residency("carol", "n3");
+        checked.trusted_issuers = Some(TrustedIssuers::known(Country::Portugal).commitment());
+        checked.freshness = Some(Freshness {
+            today: Date::new(2025, 1, 7).unwrap(),
+            max_age_days: 90,
+        });
+        let mut unchecked = checked.clone();
+        unchecked.trusted_issuers = Some(TrustedIssuers::from(&[][..]).commitment());
+        assert_eq!(strict.register(&unchecked), Err(RegistryError::PolicyMismatch));
+        let mut stale = checked.clone();
+        stale.freshness = Some(Freshness {
+            max_age_days: 365,
+            ..checked.freshness.unwrap()
+        });
+        assert_eq!(strict.register(&stale), Err(RegistryError::PolicyMismatch));
+        stale.freshness = None;
+        assert_eq!(strict.register(&stale), Err(RegistryError::PolicyMismatch));
+        assert!(strict.register(&checked).is_ok());
+
+        // failed transitions leave the state untouched
+        assert_eq!(registry.root(), root);
+    }
+
+    #[test]
+    fn action_must_match_claim() {
+        let mut registry = ResidencyRegistry::new();
+        let register = |scope| ResidencyAction::Register {
+            country: Country::Portugal,
+            scope,
+        };
+
+        assert_eq!(
+            registry.apply(&register(Scope::Region), &residency("alice", "n1")),
+            Err(RegistryError::ActionMismatch)
+        );
+        assert!(registry.apply(&register(Scope::City), &residency("alice", "n1")).is_ok());
+    }
+
+    #[test]
+    fn renewal_extends_registration() {
+        let mut registry = ResidencyRegistry::new();
+        assert_eq!(
+            registry.renew(&residency("alice", "n1")),
+            Err(RegistryError::NotRegistered)
+        );
+        registry.register(&residency("alice", "n1")).unwrap();
+        let root = registry.root();
+
+        // a bill of the same date, or one already used, does not extend anything
+        assert_eq!(
+            registry.renew(&residency("alice", "n2")),
+            Err(RegistryError::NoExtension)
+        );
+        let mut newer = residency("alice", "n1");
+        newer.expiry = Date::new(2026, 3, 1).unwrap();
+        assert_eq!(registry.renew(&newer), Err(RegistryError::NullifierUsed));
+        assert_eq!(registry.root(), root);
+
+        newer.nullifiers = vec!["n2".to_string()];
+        newer.location = "Lisboa".to_string();
+        let entry = registry.renew(&newer).unwrap();
+        assert_eq!(entry.location, "Lisboa");
+        assert_eq!(entry.expiry, Date::new(2026, 3, 1).unwrap());
+        assert_eq!(registry.len(), 1);
+        assert_ne!(registry.root(), root);
+    }
+
+    #[test]
+    fn holder_revokes_with_identity_secret() {
+        let holder = Holder {
+            id: HolderId::TaxId("123456789".to_string()),
+            salt: [7; 32],
+        };
+        let identity = holder.commitment();
+        let mut registry = ResidencyRegistry::new();
+        registry.register(&residency(&identity, "n1")).unwrap();
+        let root = registry.root();
+
+        // the same identity under another salt does not open the commitment
+        let guess = Authorization::Holder(Holder { salt: [8; 32], ..holder.clone() });
+        assert_eq!(registry.revoke(&identity, &guess), Err(RegistryError::Unauthorized));
+        assert_eq!(registry.root(), root);
+
+        let entry = registry.revoke(&identity, &Authorization::Holder(holder.clone())).unwrap();
+        assert_eq!(entry.identity, identity);
+        assert!(registry.get(&identity).is_none());
+        assert_eq!(
+            registry.revoke(&identity, &Authorization::Holder(holder)),
+            Err(RegistryError::NotRegistered)
+        );
+
+        // the bill stays spent
+        assert_eq!(
+            registry.register(&residency(&identity, "n1")),
+            Err(RegistryError::NullifierUsed)
+        );
+    }
+
+    #[test]
+    fn admin_revokes_with_authorized_key() {
+        let admin = SigningKey::from_bytes(&[1; 32]);
+        let outsider = SigningKey::from_bytes(&[2; 32]);
+        let mut registry = ResidencyRegistry::with_admins([admin.verifying_key().to_bytes()]);
+        assert_ne!(registry.root(), ResidencyRegistry::new().root());
+        registry.register(&residency("alice", "n1")).unwrap();
+        registry.register(&residency("bob", "n2")).unwrap();
+
+        let sign = |key: &SigningKey, root: &[u8; 32], identity: &str| Authorization::Admin {
+            key: key.verifying_key().to_bytes(),
+            signature: key.sign(&revocation_message(root, identity)).to_vec(),
+        };
+        let root = registry.root();
+
+        // signatures by outsiders, or for another holder, are rejected
+        assert_eq!(
+            registry.revoke("alice", &sign(&outsider, &root, "alice")),
+            Err(RegistryError::Unauthorized)
+        );
+        assert_eq!(
+            registry.revoke("alice", &sign(&admin, &root, "bob")),
+            Err(RegistryError::Unauthorized)
+        );
+        let Authorization::Admin { signature, .. } = sign(&outsider, &root, "alice") else {
+            unreachable!()
+        };
+        let forged = Authorization::Admin {
+            key: admin.verifying_key().to_bytes(),
+            signature,
+        };
+        assert_eq!(registry.revoke("alice", &forged), Err(RegistryError::Unauthorized));
+
+        let revocation = sign(&admin, &root, "alice");
+        registry.revoke("alice", &revocation).unwrap();
+        assert!(registry.get("alice").is_none());
+        assert!(registry.get("bob").is_some());
+
+        // once the state moved on, the signature cannot be replayed
+        registry.register(&residency("alice", "n3")).unwrap();
+        assert_eq!(registry.revoke("alice", &revocation), Err(RegistryError::Unauthorized));
+    }
+
+    #[test]
+    fn proves_membership() {
+        let mut registry = ResidencyRegistry::new();
+        for (i, holder) in ["alice", "bob", "carol", "dave", "erin"].iter().enumerate() {
+            registry.register(&residency(holder, &i.to_string())).unwrap();
+        }
+        let root = registry.root();
+
+        for holder in ["alice", "carol", "erin"] {
+            let entry = registry.get(holder).unwrap();
+            let proof = registry.prove(holder).unwrap();
+            assert!(proof.verify(&root, entry));
+
+            let mut forged = entry.clone();
+            forged.location = "Lisboa".to_string();
+            assert!(!proof.verify(&root, &forged));
+        }
+        assert!(registry.prove("mallory").is_none());
+    }
+
+    #[test]
+    fn witness_follows_the_registry() {
+        let admin = SigningKey::from_bytes(&[1; 32]);
+        let mut registry = ResidencyRegistry::with_admins([admin.verifying_key().to_bytes()]);
+        registry.register(&residency("alice", "n1")).unwrap();
+        registry.register(&residency("bob", "n2")).unwrap();
+
+        let mut combined = residency("carol", "n3");
+        combined.nullifiers.push("n4".to_string());
+        let mut newer = residency("alice", "n5");
+        newer.expiry = Date::new(2026, 3, 1).unwrap();
+        let register = ResidencyAction::Register {
+            country: Country::Portugal,
+            scope: Scope::City,
+        };
+        let renew = ResidencyAction::Renew {
+            country: Country::Portugal,
+            scope: Scope::City,
+        };
+        let transitions = [
+            (&register, combined),
+            (&register, residency("dave", "n1")),
+            (&register, residency("bob", "n6")),
+            (&renew, residency("erin", "n6")),
+            (&renew, newer),
+        ];
+        for (action, residency) in transitions {
+            let identity = residency.holder.clone().unwrap();
+            let mut witness = registry.witness(&identity, &residency.nullifiers, None);
+            assert_eq!(witness.root(), registry.root());
+            assert_eq!(witness.apply(action, &residency), registry.apply(action, &residency));
+            assert_eq!(witness.root(), registry.root());
+        }
+        assert_eq!(registry.len(), 3);
+
+        let root = registry.root();
+        let revocation = Authorization::Admin {
+            key: admin.verifying_key().to_bytes(),
+            signature: admin.sign(&revocation_message(&root, "bob")).to_vec(),
+        };
+        let mut witness = registry.witness("bob", &[], Some(&admin.verifying_key().to_bytes()));
+        assert_eq!(witness.revoke("bob", &revocation), registry.revoke("bob", &revocation));
+        assert_eq!(witness.root(), registry.root());
+        assert_ne!(registry.root(), root);
+    }
+
+    #[test]
+    fn rejects_witnesses_of_other_state() {
+        let admin = [1; 32];
+        let mut registry = ResidencyRegistry::with_admins([admin]);
+        registry.register(&residency("alice", "n1")).unwrap();
+        let bill = residency("bob", "n2");
+
+        // the witness of another holder, or of fewer nullifiers, than the transaction touches
+        let mut witness = registry.witness("alice", &bill.nullifiers, None);
+        assert_eq!(witness.register(&bill), Err(RegistryError::InvalidWitness));
+        let mut witness = registry.witness("bob", &[], None);
+        assert_eq!(witness.register(&bill), Err(RegistryError::InvalidWitness));
+
+        // hiding the registration of a holder, or the use of a nullifier, does not match the root
+        let mut witness = registry.witness("alice", &bill.nullifiers, None);
+        witness.entry = None;
+        assert_eq!(
+            witness.register(&residency("alice", "n2")),
+            Err(RegistryError::InvalidWitness)
+        );
+        let mut witness = registry.witness("bob", &["n1".to_string()], None);
+        witness.nullifier_paths = registry.witness("bob", &bill.nullifiers, None).nullifier_paths;
+        assert_eq!(
+            witness.register(&residency("bob", "n1")),
+            Err(RegistryError::InvalidWitness)
+        );
+
+        // an admin key has to be proven one of the admins
+        let revocation = Authorization::Admin {
+            key: admin,
+            signature: vec![0; 64],
+        };
+        let mut witness = registry.witness("alice", &[], None);
+        assert_eq!(witness.revoke("alice", &revocation), Err(RegistryError::InvalidWitness));
+        let mut witness = registry.witness("alice", &[], Some(&[2; 32]));
+        assert_eq!(witness.revoke("alice", &revocation), Err(RegistryError::InvalidWitness));
+    }
+}
@@ -0,0 +1,161 @@
+// prover/registry/src/merkle.rs
+//! Sparse SHA-256 Merkle trees of depth 256, with a leaf at the hash of every key. A leaf is
+//! proven present or absent by its path alone, so the program only needs the paths of the keys a
+//! transaction touches, not the whole tree.
+//!
+//! Empty subtrees hash to zero at every level, which keeps the paths of sparse trees short. Leaves
+//! and inner nodes are hashed with distinct prefixes, so that one can never be passed off as the
+//! other.
+use crate::Entry;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+const LEAF: u8 = 0;
+const NODE: u8 = 1;
+const STATE: u8 = 2;
+
+/// The number of levels below the root, one per bit of a key.
+const DEPTH: usize = 256;
+
+/// The hash of an empty subtree, and the root of a tree without leaves.
+const EMPTY: [u8; 32] = [0; 32];
+
+/// The siblings of the path to a key, from which the root of the tree follows given the leaf at
+/// that key, or its absence.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    /// One bit per level from the root down, set where the sibling is not an empty subtree.
+    occupied: [u8; 32],
+    /// The siblings that are not empty subtrees, from the root down.
+    siblings: Vec<[u8; 32]>,
+}
+
+impl MerkleProof {
+    /// The root of the tree with `leaf` at `key`, or no leaf at all if `None`.
+    pub fn root(&self, key: &[u8; 32], leaf: Option<&[u8; 32]>) -> [u8; 32] {
+        let mut siblings = self.siblings.iter().rev();
+        let mut hash = *leaf.unwrap_or(&EMPTY);
+        for depth in (0..DEPTH).rev() {
+            let sibling = if bit(&self.occupied, depth) {
+                *siblings.next().unwrap_or(&EMPTY)
+            } else {
+                EMPTY
+            };
+            hash = if bit(key, depth) {
+                node(&sibling, &hash)
+            } else {
+                node(&hash, &sibling)
+            };
+        }
+        hash
+    }
+}
+
+/// Shows that an entry is part of a registry, given only the registry root.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MembershipProof {
+    /// The path to the entry in the entries tree.
+    pub path: MerkleProof,
+    /// The roots of the nullifiers and admins trees, and the hash of the policy, which are bound
+    /// to the entries root in the state root.
+    pub nullifiers_root: [u8; 32],
+    pub admins_root: [u8; 32],
+    pub policy_hash: [u8; 32],
+}
+
+impl MembershipProof {
+    pub fn verify(&self, root: &[u8; 32], entry: &Entry) -> bool {
+        let entries_root = self
+            .path
+            .root(&key(entry.identity.as_bytes()), Some(&entry_leaf(entry)));
+        state_root(&entries_root, &self.nullifiers_root, &self.admins_root, &self.policy_hash)
+            == *root
+    }
+}
+
+/// Where the leaf of `data` sits in a tree.
+pub(crate) fn key(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+pub(crate) fn leaf(data: &[u8]) -> [u8; 32] {
+    Sha256::new()
+        .chain_update([LEAF])
+        .chain_update(data)
+        .finalize()
+        .into()
+}
+
+pub(crate) fn entry_leaf(entry: &Entry) -> [u8; 32] {
+    leaf(&serde_json::to_vec(entry).expect("entries always serialize"))
+}
+
+fn node(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    if *left == EMPTY && *right == EMPTY {
+        return EMPTY;
+    }
+    Sha256::new()
+        .chain_update([NODE])
+        .chain_update(left)
+        .chain_update(right)
+        .finalize()
+        .into()
+}
+
+pub(crate) fn state_root(
+    entries_root: &[u8; 32],
+    nullifiers_root: &[u8; 32],
+    admins_root: &[u8; 32],
+    policy_hash: &[u8; 32],
+) -> [u8; 32] {
+    Sha256::new()
+        .chain_update([STATE])
+        .chain_update(entries_root)
+        .chain_update(nullifiers_root)
+        .chain_update(admins_root)
+        .chain_update(policy_hash)
+        .finalize()
+        .into()
+}
+
+/// The root of the tree of `leaves`, pairs of a key and its leaf sorted by key.
+pub(crate) fn root(leaves: &[([u8; 32], [u8; 32])]) -> [u8; 32] {
+    subtree(leaves, 0)
+}
+
+/// The path to `key` in the tree of `leaves`, sorted by key, whether or not it has a leaf.
+pub(crate) fn prove(leaves: &[([u8; 32], [u8; 32])], key: &[u8; 32]) -> MerkleProof {
+    let mut proof = MerkleProof::default();
+    let mut leaves = leaves;
+    for depth in 0..DEPTH {
+        let (left, right) = leaves.split_at(leaves.partition_point(|(k, _)| !bit(k, depth)));
+        let (sibling, next) = if bit(key, depth) {
+            (subtree(left, depth + 1), right)
+        } else {
+            (subtree(right, depth + 1), left)
+        };
+        if sibling != EMPTY {
+            proof.occupied[depth / 8] |= 0x80 >> (depth % 8);
+            proof.siblings.push(sibling);
+        }
+        leaves = next;
+    }
+    proof
+}
+
+// The hash of the subtree at `depth` holding `leaves`, whose keys share their first `depth` bits
+fn subtree(leaves: &[([u8; 32], [u8; 32])], depth: usize) -> [u8; 32] {
+    match leaves {
+        [] => EMPTY,
+        [(_, leaf)] if depth == DEPTH => *leaf,
+        _ => {
+            let (left, right) = leaves.split_at(leaves.partition_point(|(k, _)| !bit(k, depth)));
+            node(&subtree(left, depth + 1), &subtree(right, depth + 1))
+        }
+    }
+}
+
+// The bit of `bytes` at `index`, from the most significant bit of the first byte
+fn bit(bytes: &[u8; 32], index: usize) -> bool {
+    bytes[index / 8] & (0x80 >> (index % 8)) != 0
+}
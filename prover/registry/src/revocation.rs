@@ -0,0 +1,55 @@
+// prover/registry/src/revocation.rs
+use crate::{RegistryError, RegistryState};
+use ed25519_dalek::{Signature, VerifyingKey};
+use prover_lib::Holder;
+use serde::{Deserialize, Serialize};
+
+/// Separates revocation signatures from any other message signed with an admin key.
+const TAG: &[u8] = b"proof-of-residency/revoke/v1";
+
+/// The right to revoke a registration, a private input of the program for
+/// [`crate::ResidencyAction::Revoke`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Authorization {
+    /// The holder withdrawing their own registration, with the identity and secret salt its
+    /// identity commitment was made from.
+    Holder(Holder),
+    /// A registry admin, with the Ed25519 signature of [`revocation_message`] by one of the
+    /// admin keys of the registry.
+    Admin { key: [u8; 32], signature: Vec<u8> },
+}
+
+impl Authorization {
+    /// Whether the authorization allows revoking `identity` from the registry `state`.
+    pub(crate) fn allows<S: RegistryState + ?Sized>(
+        &self,
+        identity: &str,
+        state: &S,
+    ) -> Result<bool, RegistryError> {
+        match self {
+            Authorization::Holder(holder) => Ok(holder.commitment() == identity),
+            Authorization::Admin { key, signature } => {
+                if !state.is_admin(key)? {
+                    return Ok(false);
+                }
+                let (Ok(key), Ok(signature)) = (
+                    VerifyingKey::from_bytes(key),
+                    Signature::from_slice(signature),
+                ) else {
+                    return Ok(false);
+                };
+                Ok(key
+                    .verify_strict(&revocation_message(&state.root(), identity), &signature)
+                    .is_ok())
+            }
+        }
+    }
+}
+
+/// What an admin signs to revoke `identity` from the registry with state `root`.
+///
+/// The root changes with every transition, so a signature cannot be replayed against a later
+/// registration of the same holder.
+pub fn revocation_message(root: &[u8; 32], identity: &str) -> Vec<u8> {
+    [TAG, root, identity.as_bytes()].concat()
+}
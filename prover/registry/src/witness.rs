@@ -0,0 +1,93 @@
+// prover/registry/src/witness.rs
+use crate::merkle::{self, MerkleProof};
+use crate::{Entry, Policy, RegistryError, RegistryState};
+use serde::{Deserialize, Serialize};
+
+/// The part of a registry a transaction touches, with the Merkle paths that tie it to the roots
+/// of the registry, see [`crate::ResidencyRegistry::witness`]. The program applies the
+/// transaction to it rather than to the whole registry, and its root follows every change.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegistryWitness {
+    pub(crate) policy: Policy,
+    pub(crate) entries_root: [u8; 32],
+    pub(crate) nullifiers_root: [u8; 32],
+    pub(crate) admins_root: [u8; 32],
+    /// The registration of the identity the transaction is about, if any, and its path.
+    pub(crate) entry: Option<Entry>,
+    pub(crate) entry_path: MerkleProof,
+    /// The paths of the nullifiers of the bills, each in the tree the previous ones were added to.
+    pub(crate) nullifier_paths: Vec<MerkleProof>,
+    /// The path of the key of the admin revoking, if any.
+    pub(crate) admin_path: Option<MerkleProof>,
+}
+
+impl RegistryWitness {
+    // The root of the entries tree with `entry` at `identity`
+    fn entries_root_with(&self, identity: &str, entry: Option<&Entry>) -> [u8; 32] {
+        let leaf = entry.map(merkle::entry_leaf);
+        self.entry_path.root(&merkle::key(identity.as_bytes()), leaf.as_ref())
+    }
+}
+
+impl RegistryState for RegistryWitness {
+    fn policy(&self) -> &Policy {
+        &self.policy
+    }
+
+    fn root(&self) -> [u8; 32] {
+        merkle::state_root(
+            &self.entries_root,
+            &self.nullifiers_root,
+            &self.admins_root,
+            &self.policy.hash(),
+        )
+    }
+
+    fn entry(&self, identity: &str) -> Result<Option<Entry>, RegistryError> {
+        let other = self.entry.as_ref().is_some_and(|entry| entry.identity != identity);
+        if other || self.entries_root_with(identity, self.entry.as_ref()) != self.entries_root {
+            return Err(RegistryError::InvalidWitness);
+        }
+        Ok(self.entry.clone())
+    }
+
+    fn is_admin(&self, key: &[u8; 32]) -> Result<bool, RegistryError> {
+        let path = self.admin_path.as_ref().ok_or(RegistryError::InvalidWitness)?;
+        let position = merkle::key(key);
+        if path.root(&position, Some(&merkle::leaf(key))) == self.admins_root {
+            Ok(true)
+        } else if path.root(&position, None) == self.admins_root {
+            Ok(false)
+        } else {
+            Err(RegistryError::InvalidWitness)
+        }
+    }
+
+    fn spend(&mut self, nullifiers: &[String]) -> Result<(), RegistryError> {
+        if nullifiers.len() != self.nullifier_paths.len() {
+            return Err(RegistryError::InvalidWitness);
+        }
+
+        let mut root = self.nullifiers_root;
+        for (nullifier, path) in nullifiers.iter().zip(&self.nullifier_paths) {
+            let key = merkle::key(nullifier.as_bytes());
+            let leaf = merkle::leaf(nullifier.as_bytes());
+            if path.root(&key, Some(&leaf)) == root {
+                return Err(RegistryError::NullifierUsed);
+            }
+            if path.root(&key, None) != root {
+                return Err(RegistryError::InvalidWitness);
+            }
+            root = path.root(&key, Some(&leaf));
+        }
+        self.nullifiers_root = root;
+        Ok(())
+    }
+
+    fn set_entry(&mut self, identity: &str, entry: Option<Entry>) -> Result<(), RegistryError> {
+        self.entry(identity)?;
+        self.entries_root = self.entries_root_with(identity, entry.as_ref());
+        self.entry = entry;
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 use prover_server::config::ServerConfig;
 use prover_server::handlers::{self, FileHandler};
 use prover_server::proof_service::{ProofService, ProverInstance};
+use prover_server::registry_store::RegistryStore;
 use std::sync::Arc;
 use warp::Filter;
 
@@ -11,7 +12,13 @@ async fn main() {
 
     // Initialize prover
     let prover = Arc::new(ProverInstance::new());
-    let proof_service = Arc::new(ProofService::new(Arc::clone(&prover)));
+    let registry = RegistryStore::open(&config.registry_path, config.admin_keys.clone())
+        .expect("Failed to open the residency registry");
+    let proof_service = Arc::new(ProofService::new(
+        Arc::clone(&prover),
+        registry,
+        config.node_url.clone(),
+    ));
     let file_handler = Arc::new(FileHandler::new(Arc::clone(&proof_service)));
 
     // Setup routes
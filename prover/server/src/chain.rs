@@ -0,0 +1,42 @@
+use crate::error::ServerError;
+use base64::Engine;
+use serde::Deserialize;
+use warp::hyper::{body, Client, Uri};
+
+#[derive(Deserialize)]
+struct ContractResponse {
+    contract: Contract,
+}
+
+#[derive(Deserialize)]
+struct Contract {
+    /// The state the contract settled to last, base64-encoded.
+    state_digest: String,
+}
+
+/// Fetches the state digest of the contract `name` from the REST API of the node at `node_url`.
+pub async fn contract_state(node_url: &str, name: &str) -> Result<Vec<u8>, ServerError> {
+    let uri: Uri = format!("{}/hyle/zktx/v1/contract/{}", node_url.trim_end_matches('/'), name)
+        .parse()
+        .map_err(|e: warp::http::uri::InvalidUri| ServerError::Chain(e.to_string()))?;
+    let response = Client::new()
+        .get(uri)
+        .await
+        .map_err(|e| ServerError::Chain(e.to_string()))?;
+    if !response.status().is_success() {
+        return Err(ServerError::Chain(format!(
+            "the node answered {} for contract {}",
+            response.status(),
+            name
+        )));
+    }
+
+    let bytes = body::to_bytes(response.into_body())
+        .await
+        .map_err(|e| ServerError::Chain(e.to_string()))?;
+    let response: ContractResponse =
+        serde_json::from_slice(&bytes).map_err(|e| ServerError::Chain(e.to_string()))?;
+    base64::engine::general_purpose::STANDARD
+        .decode(response.contract.state_digest)
+        .map_err(|e| ServerError::Chain(e.to_string()))
+}
@@ -1,9 +1,16 @@
 use serde::Deserialize;
+use std::path::PathBuf;
 
 #[derive(Debug, Deserialize)]
 pub struct ServerConfig {
     pub port: u16,
     pub max_file_size: u64,
+    /// The Ed25519 keys allowed to revoke any registration, see `--admin-key`.
+    pub admin_keys: Vec<[u8; 32]>,
+    /// Where the residency registry is saved, see `--registry`.
+    pub registry_path: PathBuf,
+    /// The REST API of the Hyle node the contract state is read from, see `--node-url`.
+    pub node_url: String,
 }
 
 impl Default for ServerConfig {
@@ -11,6 +18,9 @@ impl Default for ServerConfig {
         Self {
             port: 8080,
             max_file_size: 5_000_000, // 5MB
+            admin_keys: Vec::new(),
+            registry_path: PathBuf::from("../temp/registry.json"),
+            node_url: "http://localhost:1317".to_string(),
         }
     }
 }
@@ -25,8 +35,32 @@ impl ServerConfig {
             .and_then(|p| p.parse().ok())
             .unwrap_or(8080);
 
+        // every `--admin-key <hex>` adds a registry admin
+        let admin_keys = args
+            .windows(2)
+            .filter(|pair| pair[0] == "--admin-key")
+            .filter_map(|pair| hex::decode(&pair[1]).ok()?.try_into().ok())
+            .collect();
+
+        let defaults = Self::default();
+        let registry_path = args
+            .iter()
+            .position(|arg| arg == "--registry")
+            .and_then(|i| args.get(i + 1))
+            .map(PathBuf::from)
+            .unwrap_or(defaults.registry_path);
+        let node_url = args
+            .iter()
+            .position(|arg| arg == "--node-url")
+            .and_then(|i| args.get(i + 1))
+            .cloned()
+            .unwrap_or(defaults.node_url);
+
         Self {
             port,
+            admin_keys,
+            registry_path,
+            node_url,
             ..Default::default()
         }
     }
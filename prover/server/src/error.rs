@@ -1,3 +1,5 @@
+use prover_lib::ResidencyError;
+use prover_registry::RegistryError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -14,6 +16,48 @@ pub enum ServerError {
     #[error("Invalid file type: {0}")]
     InvalidFileType(String),
     
+    #[error("Invalid country: {0}")]
+    InvalidCountry(String),
+    
+    #[error("Invalid scope: {0}")]
+    InvalidScope(String),
+    
+    #[error("Invalid maximum age: {0}")]
+    InvalidMaxAge(String),
+    
+    #[error("Invalid action: {0}")]
+    InvalidAction(String),
+    
+    #[error("Invalid residency period: {0}")]
+    InvalidResidencyPeriod(String),
+    
+    #[error("Invalid disclosure: {0}")]
+    InvalidDisclosure(String),
+    
+    #[error("Invalid address salt: {0}")]
+    InvalidAddressSalt(String),
+    
+    #[error("Invalid authorization: {0}")]
+    InvalidAuthorization(String),
+    
+    #[error("Invalid blobs: {0}")]
+    InvalidBlobs(String),
+    
+    #[error("Invalid holder: {0}")]
+    InvalidHolder(String),
+    
+    #[error("Invalid DKIM key: {0}")]
+    InvalidDkimKey(String),
+    
+    #[error("Bill rejected: {0}")]
+    Rejected(#[from] ResidencyError),
+    
+    #[error("Registry update rejected: {0}")]
+    Registry(#[from] RegistryError),
+    
+    #[error("Failed to query the chain: {0}")]
+    Chain(String),
+    
     #[error("Failed to read file: {0}")]
     FileReadError(String),
     
@@ -1,8 +1,12 @@
 use crate::error::ServerError;
 use crate::proof_service::ProofService;
+use crate::types::{Blob, UploadRequest};
 use bytes::BufMut;
 use futures::{StreamExt, TryStreamExt};
+use prover_lib::{Country, Date, Freshness, Holder, HolderId, Scope};
+use prover_registry::{Authorization, ResidencyAction, CONTRACT_NAME};
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use warp::{
     filters::multipart::FormData,
     http::StatusCode,
@@ -20,31 +24,45 @@ impl FileHandler {
     }
 
     pub async fn handle_upload(&self, form: FormData) -> Result<impl Reply, Rejection> {
-        let (file_content, tx_hash) = self.extract_form_data(form).await?;
+        let request = self.extract_form_data(form).await?;
         
         let proof_response = self.proof_service
-            .generate_proof(file_content, tx_hash)
+            .generate_proof(request)
             .await
-            .map_err(|e| warp::reject::custom(e))?;
+            .map_err(warp::reject::custom)?;
 
-        Ok(warp::reply::with_header(
-            proof_response.proof,
-            "Content-Type",
-            "application/octet-stream"
-        ))
+        Ok(warp::reply::json(&proof_response))
     }
 
-    async fn extract_form_data(&self, mut form: FormData) -> Result<(Vec<u8>, String), Rejection> {
-        let mut file_content: Option<Vec<u8>> = None;
+    async fn extract_form_data(&self, mut form: FormData) -> Result<UploadRequest, Rejection> {
+        let mut documents: Vec<Vec<u8>> = Vec::new();
         let mut tx_hash: Option<String> = None;
+        let mut country = Country::Portugal;
+        let mut scope = Scope::Country;
+        let mut action = "register".to_string();
+        let mut identity = None;
+        let mut known_issuers_only = false;
+        let mut dkim_key = None;
+        let mut freshness = None;
+        let mut holder_id = None;
+        let mut holder_salt = None;
+        let mut blobs = None;
+        let mut blob_index = 0;
+        let mut address_salt = None;
+        let mut disclosure = None;
+        let mut min_residency_months = None;
+        let mut admin_key = None;
+        let mut admin_signature = None;
 
         while let Some(Ok(part)) = form.next().await {
             match part.name() {
                 "file" => {
                     if let Some(file_type) = part.content_type() {
                         match file_type {
-                            "application/pdf" => {
-                                file_content = Some(self.read_file_content(part).await?);
+                            // every file is one more bill for the claim, either the PDF or the
+                            // email it was delivered with
+                            "application/pdf" | "message/rfc822" => {
+                                documents.push(self.read_file_content(part).await?);
                             }
                             _ => {
                                 return Err(warp::reject::custom(ServerError::InvalidFileType(
@@ -55,22 +73,203 @@ impl FileHandler {
                     }
                 }
                 "tx_hash" => {
+                    tx_hash = Some(self.read_text(part).await?);
+                }
+                "country" => {
+                    let value = self.read_text(part).await?;
+                    country = value
+                        .parse()
+                        .map_err(|_| warp::reject::custom(ServerError::InvalidCountry(value)))?;
+                }
+                "scope" => {
+                    let value = self.read_text(part).await?;
+                    scope = value
+                        .parse()
+                        .map_err(|_| warp::reject::custom(ServerError::InvalidScope(value)))?;
+                }
+                "action" => {
+                    let value = self.read_text(part).await?;
+                    action = match value.trim() {
+                        "register" | "renew" | "revoke" => value.trim().to_string(),
+                        _ => return Err(warp::reject::custom(ServerError::InvalidAction(value))),
+                    };
+                }
+                "blobs" => {
+                    let value = self.read_text(part).await?;
+                    blobs = Some(serde_json::from_str::<Vec<Blob>>(&value).map_err(|e| {
+                        warp::reject::custom(ServerError::InvalidBlobs(e.to_string()))
+                    })?);
+                }
+                "blob_index" => {
+                    let value = self.read_text(part).await?;
+                    blob_index = value
+                        .trim()
+                        .parse()
+                        .map_err(|_| warp::reject::custom(ServerError::InvalidBlobs(value)))?;
+                }
+                "known_issuers_only" => {
                     let bytes = self.read_file_content(part).await?;
-                    tx_hash = Some(String::from_utf8(bytes)
-                        .map_err(|e| warp::reject::custom(ServerError::FileReadError(e.to_string())))?);
+                    known_issuers_only = bytes == b"true";
+                }
+                "dkim_key" => {
+                    // the TXT record of the key, the client looks it up from the selector and
+                    // domain of the signature
+                    let value = self.read_text(part).await?;
+                    dkim_key = Some(value.parse().map_err(|e| {
+                        warp::reject::custom(ServerError::InvalidDkimKey(e))
+                    })?);
+                }
+                "max_age_days" => {
+                    let value = self.read_text(part).await?;
+                    let max_age_days = value
+                        .trim()
+                        .parse()
+                        .map_err(|_| warp::reject::custom(ServerError::InvalidMaxAge(value)))?;
+                    let now = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map_err(|e| warp::reject::custom(ServerError::Internal(e.to_string())))?;
+                    freshness = Some(Freshness {
+                        today: Date::from_unix_timestamp(now.as_secs()),
+                        max_age_days,
+                    });
+                }
+                "min_residency_months" => {
+                    let value = self.read_text(part).await?;
+                    min_residency_months = Some(value.trim().parse().map_err(|_| {
+                        warp::reject::custom(ServerError::InvalidResidencyPeriod(value))
+                    })?);
+                }
+                "disclose" => {
+                    let value = self.read_text(part).await?;
+                    disclosure = Some(value.parse().map_err(|e| {
+                        warp::reject::custom(ServerError::InvalidDisclosure(e))
+                    })?);
+                }
+                "address_salt" => {
+                    let value = self.read_text(part).await?;
+                    address_salt = Some(
+                        hex::decode(value.trim())
+                            .ok()
+                            .and_then(|salt| <[u8; 32]>::try_from(salt).ok())
+                            .ok_or_else(|| warp::reject::custom(ServerError::InvalidAddressSalt(
+                                "the salt must be 32 hex-encoded bytes".to_string(),
+                            )))?,
+                    );
+                }
+                "identity" => {
+                    identity = Some(self.read_text(part).await?.trim().to_string());
+                }
+                "admin_key" => {
+                    let value = self.read_text(part).await?;
+                    admin_key = Some(
+                        hex::decode(value.trim())
+                            .ok()
+                            .and_then(|key| <[u8; 32]>::try_from(key).ok())
+                            .ok_or_else(|| warp::reject::custom(ServerError::InvalidAuthorization(
+                                "the admin key must be 32 hex-encoded bytes".to_string(),
+                            )))?,
+                    );
+                }
+                "admin_signature" => {
+                    let value = self.read_text(part).await?;
+                    admin_signature = Some(hex::decode(value.trim()).map_err(|e| {
+                        warp::reject::custom(ServerError::InvalidAuthorization(e.to_string()))
+                    })?);
+                }
+                "holder_name" => {
+                    holder_id = Some(HolderId::Name(self.read_text(part).await?));
+                }
+                "holder_nif" => {
+                    holder_id = Some(HolderId::TaxId(self.read_text(part).await?.trim().to_string()));
+                }
+                "holder_salt" => {
+                    let value = self.read_text(part).await?;
+                    let salt = hex::decode(value.trim())
+                        .ok()
+                        .and_then(|salt| <[u8; 32]>::try_from(salt).ok())
+                        .ok_or_else(|| warp::reject::custom(ServerError::InvalidHolder(
+                            "the salt must be 32 hex-encoded bytes".to_string(),
+                        )))?;
+                    holder_salt = Some(salt);
                 }
                 _ => {}
             }
         }
 
-        match (file_content, tx_hash) {
-            (Some(content), Some(hash)) => Ok((content, hash)),
+        let holder = match (holder_id, holder_salt) {
+            (Some(id), Some(salt)) => Some(Holder { id, salt }),
+            (None, None) => None,
+            _ => {
+                return Err(warp::reject::custom(ServerError::InvalidHolder(
+                    "both an identity and a salt are required".to_string(),
+                )));
+            }
+        };
+
+        let authorization = match (admin_key, admin_signature) {
+            (Some(key), Some(signature)) => Some(Authorization::Admin { key, signature }),
+            (None, None) => holder.clone().map(Authorization::Holder),
+            _ => {
+                return Err(warp::reject::custom(ServerError::InvalidAuthorization(
+                    "both an admin key and a signature are required".to_string(),
+                )));
+            }
+        };
+
+        // without the blobs of the transaction, assume it only holds the given action
+        let blobs = match blobs {
+            Some(blobs) => blobs,
+            None => {
+                let action = match action.as_str() {
+                    "renew" => ResidencyAction::Renew { country, scope },
+                    "revoke" => ResidencyAction::Revoke {
+                        identity: identity
+                            .or_else(|| holder.as_ref().map(Holder::commitment))
+                            .ok_or_else(|| warp::reject::custom(ServerError::InvalidAuthorization(
+                                "the identity to revoke is required".to_string(),
+                            )))?,
+                    },
+                    _ => ResidencyAction::Register { country, scope },
+                };
+                vec![Blob {
+                    contract_name: CONTRACT_NAME.to_string(),
+                    data: action.encode(),
+                }]
+            }
+        };
+
+        // revocations take no bill
+        let revocation = blobs
+            .get(blob_index as usize)
+            .and_then(|blob| ResidencyAction::from_blob(&blob.contract_name, &blob.data).ok())
+            .is_some_and(|action| action.proven_scope().is_none());
+        match (documents.is_empty() && !revocation, tx_hash) {
+            (false, Some(tx_hash)) => Ok(UploadRequest {
+                documents,
+                tx_hash,
+                blobs,
+                blob_index,
+                known_issuers_only,
+                freshness,
+                holder,
+                address_salt,
+                disclosure,
+                min_residency_months,
+                dkim_key,
+                authorization,
+            }),
             _ => Err(warp::reject::custom(ServerError::FileReadError(
                 "Missing required fields".to_string(),
             ))),
         }
     }
 
+    async fn read_text(&self, part: warp::multipart::Part) -> Result<String, Rejection> {
+        let bytes = self.read_file_content(part).await?;
+        String::from_utf8(bytes)
+            .map_err(|e| warp::reject::custom(ServerError::FileReadError(e.to_string())))
+    }
+
     async fn read_file_content(
         &self,
         part: warp::multipart::Part,
@@ -91,8 +290,11 @@ pub async fn get_verification_key(
     proof_service: Arc<ProofService>,
 ) -> Result<impl Reply, Rejection> {
     let vk = proof_service.get_verification_key();
+    // the contract has to be registered with the root of the registry the server starts from
+    let initial_state = proof_service.registry_root().await;
     Ok(warp::reply::json(&serde_json::json!({
-        "verification_key": vk
+        "verification_key": vk,
+        "initial_state": hex::encode(initial_state),
     })))
 }
 
@@ -107,6 +309,62 @@ pub async fn handle_rejection(err: Rejection) -> Result<impl Reply, std::convert
                 StatusCode::BAD_REQUEST,
                 format!("Invalid file type: {}", t),
             ),
+            ServerError::InvalidCountry(c) => (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid country: {}", c),
+            ),
+            ServerError::InvalidScope(s) => (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid scope: {}", s),
+            ),
+            ServerError::InvalidMaxAge(a) => (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid maximum age: {}", a),
+            ),
+            ServerError::InvalidAction(a) => (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid action: {}", a),
+            ),
+            ServerError::InvalidResidencyPeriod(p) => (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid residency period: {}", p),
+            ),
+            ServerError::InvalidDisclosure(d) => (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid disclosure: {}", d),
+            ),
+            ServerError::InvalidAddressSalt(s) => (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid address salt: {}", s),
+            ),
+            ServerError::InvalidAuthorization(a) => (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid authorization: {}", a),
+            ),
+            ServerError::InvalidBlobs(b) => (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid blobs: {}", b),
+            ),
+            ServerError::InvalidHolder(h) => (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid holder: {}", h),
+            ),
+            ServerError::InvalidDkimKey(k) => (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid DKIM key: {}", k),
+            ),
+            ServerError::Rejected(e) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("Bill rejected: {}", e),
+            ),
+            ServerError::Registry(e) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("Registry update rejected: {}", e),
+            ),
+            ServerError::Chain(e) => (
+                StatusCode::BAD_GATEWAY,
+                format!("Failed to query the chain: {}", e),
+            ),
             ServerError::FileReadError(e) => (
                 StatusCode::BAD_REQUEST,
                 format!("Error reading file: {}", e),
@@ -1,5 +1,7 @@
+pub mod chain;
 pub mod config;
 pub mod error;
 pub mod handlers;
 pub mod proof_service;
+pub mod registry_store;
 pub mod types;
\ No newline at end of file
@@ -1,10 +1,16 @@
+use crate::chain;
 use crate::error::ServerError;
-use crate::types::ProofResponse;
+use crate::registry_store::RegistryStore;
+use crate::types::{HyleOutput, ProofResponse, UploadRequest};
+use base64::Engine;
+use prover_lib::{Config, TrustedIssuers};
+use prover_registry::{Authorization, RegistryError, RegistryState, ResidencyAction};
 use sp1_sdk::{
     include_elf, network::proto::network::ProofMode, HashableKey, NetworkProverV1, ProverClient,
     SP1ProofWithPublicValues, SP1ProvingKey, SP1Stdin, SP1VerifyingKey,
 };
 use std::sync::Arc;
+use tokio::sync::Mutex;
 
 const REGEX_IO_ELF: &[u8] = include_elf!("prover-program");
 
@@ -27,35 +33,154 @@ impl ProverInstance {
     }
 }
 
+impl Default for ProverInstance {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub struct ProofService {
     prover: Arc<ProverInstance>,
+    /// The registry whose root is the current state of the contract.
+    registry: Mutex<RegistryStore>,
+    /// The node the contract state is read from before every proof.
+    node_url: String,
 }
 
 impl ProofService {
-    pub fn new(prover: Arc<ProverInstance>) -> Self {
-        Self { prover }
+    pub fn new(prover: Arc<ProverInstance>, registry: RegistryStore, node_url: String) -> Self {
+        Self {
+            prover,
+            registry: Mutex::new(registry),
+            node_url,
+        }
     }
 
     pub fn get_verification_key(&self) -> String {
         self.prover.verification_key()
     }
 
+    /// The root of the registry the next proof starts from.
+    pub async fn registry_root(&self) -> [u8; 32] {
+        self.registry.lock().await.settled().root()
+    }
+
     pub async fn generate_proof(
         &self,
-        file_content: Vec<u8>,
-        tx_hash: String,
+        request: UploadRequest,
     ) -> Result<ProofResponse, ServerError> {
+        let UploadRequest {
+            documents,
+            tx_hash,
+            blobs,
+            blob_index,
+            known_issuers_only,
+            freshness,
+            holder,
+            address_salt,
+            disclosure,
+            min_residency_months,
+            dkim_key,
+            authorization,
+        } = request;
+
+        // proofs are generated one at a time, from the state the contract settled to
+        let mut registry = self.registry.lock().await;
+        let contract_name = blobs
+            .get(blob_index as usize)
+            .map(|blob| blob.contract_name.as_str())
+            .ok_or_else(|| ServerError::InvalidBlobs("no blob at the blob index".to_string()))?;
+        let state = chain::contract_state(&self.node_url, contract_name).await?;
+        registry.sync(&state)?;
+
+        // validate the bills natively first, so a rejected bill gets the reason back rather than
+        // an opaque proving failure
+        let action = blobs
+            .get(blob_index as usize)
+            .and_then(|blob| ResidencyAction::from_blob(&blob.contract_name, &blob.data).ok());
+        let residency = match action {
+            Some(
+                ResidencyAction::Register { country, scope }
+                | ResidencyAction::Renew { country, scope },
+            ) => {
+                let config = Config {
+                    scope,
+                    country,
+                    trusted_issuers: (known_issuers_only
+                        || registry.settled().policy().known_issuers_only)
+                        .then(|| TrustedIssuers::known(country)),
+                    freshness,
+                    holder: holder.clone(),
+                    nullifier_domain: registry.settled().policy().nullifier_domain.clone(),
+                    validity_days: registry.settled().policy().validity_days,
+                    address_salt,
+                    disclosure,
+                    min_residency_months,
+                    trusted_roots: None,
+                    dkim_key: dkim_key.clone(),
+                };
+                Some(prover_lib::run_all(&documents, config)?)
+            }
+            _ => None,
+        };
+
+        // replay the transition the program proves, so a rejected one is reported before proving
+        let mut next = registry.settled().clone();
+        // the program only gets the part of the registry the transition touches
+        let witness = match (&action, &residency) {
+            (Some(ResidencyAction::Revoke { identity }), _) => {
+                let authorization = authorization.as_ref().ok_or(RegistryError::Unauthorized)?;
+                let admin = match authorization {
+                    Authorization::Admin { key, .. } => Some(key),
+                    Authorization::Holder(_) => None,
+                };
+                let witness = registry.settled().witness(identity, &[], admin);
+                next.revoke(identity, authorization)?;
+                witness
+            }
+            (Some(action), Some(residency)) => {
+                next.apply(action, residency)?;
+                let identity = residency.holder.as_deref().unwrap_or_default();
+                registry.settled().witness(identity, &residency.nullifiers, None)
+            }
+            _ => return Err(RegistryError::InvalidAction.into()),
+        };
+
         let mut stdin = SP1Stdin::new();
 
-        stdin.write(&file_content);
+        stdin.write(&documents);
         stdin.write(&tx_hash);
+        stdin.write(&blobs);
+        stdin.write(&blob_index);
+        stdin.write(&known_issuers_only);
+        stdin.write(&freshness);
+        stdin.write(&holder);
+        stdin.write(&address_salt);
+        stdin.write(&disclosure);
+        stdin.write(&min_residency_months);
+        stdin.write(&dkim_key);
+        stdin.write(&witness);
+        stdin.write(&authorization);
 
         let mut proof = match std::env::var("SP1_PROVER").as_deref() {
             Ok("network") => self.generate_network_proof(stdin).await?,
             _ => self.generate_local_proof(stdin)?,
         };
 
-        let verification_result = proof.public_values.read::<bool>();
+        let output = proof.public_values.read::<HyleOutput>();
+        let program_outputs = serde_json::from_slice(&output.program_outputs)
+            .map_err(|e| ServerError::Internal(e.to_string()))?;
+
+        // the contract only moves to the new registry once the proof settles
+        if output.success {
+            if output.next_state != next.root() {
+                return Err(ServerError::Internal(
+                    "the proven state is not the root of the updated registry".to_string(),
+                ));
+            }
+            registry.propose(next);
+        }
+
         proof
             .save("../temp/proof-with-pis.bin")
             .map_err(|e| ServerError::Internal(e.to_string()))?;
@@ -64,9 +189,9 @@ impl ProofService {
             .map_err(|e| ServerError::Internal(e.to_string()))?;
 
         Ok(ProofResponse {
-            success: true,
-            result: verification_result,
-            proof: proof_bytes,
+            success: output.success,
+            program_outputs,
+            proof: base64::engine::general_purpose::STANDARD.encode(proof_bytes),
             tx_hash,
             vk: self.prover.verification_key(),
         })
@@ -0,0 +1,78 @@
+use crate::error::ServerError;
+use prover_registry::{RegistryState, ResidencyRegistry};
+use std::path::PathBuf;
+
+/// The registry the contract state is the root of, saved to disk so the server picks up where
+/// the chain is after a restart.
+///
+/// A proof only moves the contract once it settles, so the registry it was generated for stays
+/// pending until the contract state is seen to be its root.
+pub struct RegistryStore {
+    path: PathBuf,
+    /// The registry whose root the contract settled to last.
+    settled: ResidencyRegistry,
+    /// The registries of the proofs generated since, at most one of them settles.
+    pending: Vec<ResidencyRegistry>,
+}
+
+impl RegistryStore {
+    /// Opens the registry saved at `path`, or starts an empty one whose registrations can be
+    /// revoked by `admin_keys`. The admins of a saved registry are part of its root, so they are
+    /// kept as they are.
+    pub fn open(path: impl Into<PathBuf>, admin_keys: Vec<[u8; 32]>) -> Result<Self, ServerError> {
+        let path = path.into();
+        let settled = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|e| ServerError::Internal(format!("invalid registry file: {}", e)))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                ResidencyRegistry::with_admins(admin_keys)
+            }
+            Err(e) => return Err(ServerError::Internal(e.to_string())),
+        };
+        Ok(Self {
+            path,
+            settled,
+            pending: Vec::new(),
+        })
+    }
+
+    /// The registry the next proof starts from.
+    pub fn settled(&self) -> &ResidencyRegistry {
+        &self.settled
+    }
+
+    /// Catches up with `state`, the state digest the contract settled to, and saves the registry
+    /// it is the root of.
+    pub fn sync(&mut self, state: &[u8]) -> Result<(), ServerError> {
+        if state == self.settled.root() {
+            return Ok(());
+        }
+        let Some(index) = self.pending.iter().position(|registry| registry.root() == state) else {
+            return Err(ServerError::Internal(format!(
+                "the contract state {} is not the root of a known registry",
+                hex::encode(state)
+            )));
+        };
+        self.settled = self.pending.swap_remove(index);
+        // the other proofs started from the previous state, they can no longer settle
+        self.pending.clear();
+        self.save()
+    }
+
+    /// Records the registry a proof moves the contract to, until the proof settles.
+    pub fn propose(&mut self, next: ResidencyRegistry) {
+        if next != self.settled {
+            self.pending.push(next);
+        }
+    }
+
+    fn save(&self) -> Result<(), ServerError> {
+        let bytes =
+            serde_json::to_vec(&self.settled).map_err(|e| ServerError::Internal(e.to_string()))?;
+        // write a copy first, so a crash never leaves a truncated registry behind
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, bytes)
+            .and_then(|_| std::fs::rename(&tmp, &self.path))
+            .map_err(|e| ServerError::Internal(e.to_string()))
+    }
+}
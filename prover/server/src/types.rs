@@ -1,10 +1,59 @@
+use prover_lib::{Disclosure, DkimKey, Freshness, Holder};
+use prover_registry::Authorization;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ProofResponse {
+    /// Whether the program accepted the bills and the action, as committed in the proof.
     pub success: bool,
-    pub result: bool,
-    pub proof: Vec<u8>,
+    /// The committed outcome: the residency claim, or the reason it was rejected.
+    pub program_outputs: serde_json::Value,
+    /// The proof with its public values, base64-encoded.
+    pub proof: String,
     pub tx_hash: String,
     pub vk: String,
-}
\ No newline at end of file
+}
+
+/// The public values committed by the program, decoded the same way as
+/// `hyle_contract_sdk::HyleOutput`, whose newtype fields encode as their inner value.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HyleOutput {
+    pub version: u32,
+    pub initial_state: Vec<u8>,
+    pub next_state: Vec<u8>,
+    pub identity: String,
+    pub tx_hash: String,
+    pub index: u32,
+    pub blobs: Vec<u8>,
+    pub success: bool,
+    /// The JSON of the claim, see `prover/program/src/main.rs`.
+    pub program_outputs: Vec<u8>,
+}
+
+/// A blob of a Hyle transaction, encoded the same way as `hyle_contract_sdk::Blob` on the program
+/// input.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Blob {
+    pub contract_name: String,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub struct UploadRequest {
+    /// The bills, combined into a single claim.
+    pub documents: Vec<Vec<u8>>,
+    pub tx_hash: String,
+    pub blobs: Vec<Blob>,
+    pub blob_index: u32,
+    pub known_issuers_only: bool,
+    pub freshness: Option<Freshness>,
+    pub holder: Option<Holder>,
+    pub address_salt: Option<[u8; 32]>,
+    pub disclosure: Option<Disclosure>,
+    pub min_residency_months: Option<u32>,
+    /// Only accept bills attached to an email signed with this DKIM key, as published in the DNS
+    /// of the sending domain.
+    pub dkim_key: Option<DkimKey>,
+    /// Only used to revoke a registration.
+    pub authorization: Option<Authorization>,
+}
@@ -38,6 +38,9 @@ export default function FileUpload() {
     const [sessionId, setSessionId] = useState<string | null>(null);
     const [proverPort, setProverPort] = useState<number | null>(null);
     const inputRef = useRef<HTMLInputElement>(null);
+    const [holderName, setHolderName] = useState<string>("");
+    const [holderNif, setHolderNif] = useState<string>("");
+    const [maxAgeDays, setMaxAgeDays] = useState<string>("");
 
     useEffect(() => {
         setupSession();
@@ -86,9 +89,40 @@ export default function FileUpload() {
             return;
         }
 
+        if (!holderName.trim() && !holderNif.trim()) {
+            setUploadState(prev => ({
+                ...prev,
+                status: UploadStatus.Error,
+                error: "Please enter the name or the NIF the bill is addressed to",
+                progress: 0
+            }));
+            return;
+        }
+
         const formData = new FormData();
         formData.append("file", file);
-        formData.append("country", "Portugal");
+        // The registry binds the residency to the holder, the prover only commits their salted hash
+        if (holderNif.trim()) {
+            formData.append("holder_nif", holderNif.trim());
+        } else {
+            formData.append("holder_name", holderName.trim());
+        }
+        formData.append("holder_salt", getHolderSalt());
+        if (maxAgeDays.trim()) {
+            formData.append("max_age_days", maxAgeDays.trim());
+        }
+
+        // The residency action the prover checks the document against
+        const blobs = [
+            {
+                contractName: "sp1_residency",
+                data: Array.from(new TextEncoder().encode(JSON.stringify({
+                    action: "register",
+                    country: "Portugal",
+                    scope: "Country",
+                }))),
+            },
+        ];
 
         try {
             // Step 1: Broadcasting blob transaction
@@ -101,12 +135,7 @@ export default function FileUpload() {
 
             const blobTxHash = await broadcastBlobTx(network, {
                 identity: "",
-                blobs: [
-                    {
-                        contractName: "sp1_residency",
-                        data: Array.from(new TextEncoder().encode("Portugal")),
-                    },
-                ]
+                blobs,
             });
 
             // Step 2: Generating proof
@@ -118,6 +147,11 @@ export default function FileUpload() {
             }));
 
             formData.append("tx_hash", blobTxHash);
+            formData.append("blobs", JSON.stringify(blobs.map(({ contractName, data }) => ({
+                contract_name: contractName,
+                data,
+            }))));
+            formData.append("blob_index", "0");
             const response = await fetch(`http://localhost:${proverPort}/upload`, {
                 method: "POST",
                 body: formData,
@@ -127,8 +161,11 @@ export default function FileUpload() {
                 throw new Error("Failed to generate proof");
             }
 
-            const proofBuffer = await response.arrayBuffer();
-            const proofBytes = new Uint8Array(proofBuffer);
+            const { success, program_outputs, proof } = await response.json();
+            if (!success) {
+                throw new Error(program_outputs?.Err ? `Proof rejected: ${JSON.stringify(program_outputs.Err)}` : "Proof rejected");
+            }
+            const proofBytes = new Uint8Array(Buffer.from(proof, "base64"));
 
             // Step 3: Broadcasting proof
             setUploadState(prev => ({
@@ -165,6 +202,20 @@ export default function FileUpload() {
         }
     };
 
+    // The salt the holder's identity is hashed with. It is kept in the browser so that renewals
+    // and revocations commit the same holder as the registration.
+    const getHolderSalt = () => {
+        const stored = localStorage.getItem("holder_salt");
+        if (stored) {
+            return stored;
+        }
+        const salt = Array.from(crypto.getRandomValues(new Uint8Array(32)))
+            .map(byte => byte.toString(16).padStart(2, "0"))
+            .join("");
+        localStorage.setItem("holder_salt", salt);
+        return salt;
+    };
+
     const getStatusColor = () => {
         switch (uploadState.status) {
             case UploadStatus.Error:
@@ -225,15 +276,40 @@ export default function FileUpload() {
             </div>
 
             <form onSubmit={handleSubmit} className="w-full">
+                <div className="mb-6 flex flex-col gap-3 text-left">
+                    <input
+                        type="text"
+                        placeholder="Name on the bill"
+                        value={holderName}
+                        onChange={e => setHolderName(e.target.value)}
+                        className="px-3 py-2 border border-gray-300 rounded"
+                    />
+                    <input
+                        type="text"
+                        inputMode="numeric"
+                        placeholder="NIF on the bill (preferred over the name)"
+                        value={holderNif}
+                        onChange={e => setHolderNif(e.target.value)}
+                        className="px-3 py-2 border border-gray-300 rounded"
+                    />
+                    <input
+                        type="number"
+                        min={1}
+                        placeholder="Maximum age of the bill in days (optional)"
+                        value={maxAgeDays}
+                        onChange={e => setMaxAgeDays(e.target.value)}
+                        className="px-3 py-2 border border-gray-300 rounded"
+                    />
+                </div>
                 <FileUploadRoot
                     alignItems="stretch"
                     maxFiles={1}
-                    accept=".pdf"
+                    accept=".pdf,.eml"
                     ref={inputRef}
                 >
                     <FileUploadDropzone
                         label="Drag and drop here to upload"
-                        description=".pdf files up to 5MB"
+                        description=".pdf or .eml files up to 5MB"
                     />
                     <FileUploadList />
 
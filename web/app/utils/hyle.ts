@@ -16,6 +16,8 @@ async function waitForServer(port: number, maxRetries = 5, delayMs = 1000): Prom
 
 interface VerificationKeyResponse {
     verification_key: string;
+    // The root of the registry the prover starts from, hex-encoded
+    initial_state: string;
 }
 
 export async function ensureContractsRegistered(proverPort: number) {
@@ -43,7 +45,7 @@ export async function ensureContractsRegistered(proverPort: number) {
             "sp1",
             "sp1_residency",
             verificationKey,
-            new Uint8Array([0, 0, 0, 0])
+            new Uint8Array(Buffer.from(data.initial_state, 'hex'))
         );
     }
 }
\ No newline at end of file